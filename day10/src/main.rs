@@ -1,4 +1,4 @@
-use std::{fs::File, io::{BufRead, BufReader}, fmt::{Error, self}, path::Display};
+use std::{fs::File, io::{BufRead, BufReader}, fmt::Error};
 
 use clap::Parser;
 
@@ -21,7 +21,22 @@ struct Args {
 
 #[derive(Debug)]
 enum ParseError {
-    Error
+    Error,
+    /// There's no S tile
+    NoStart,
+    /// S has to connect to exactly two neighbours to be part of a loop
+    StartConnections { x: usize, y: usize, connections: usize },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "couldn't read input"),
+            Self::NoStart => write!(f, "no S tile in the map"),
+            Self::StartConnections { x, y, connections } => write!(f,
+                "S at x: {} y: {} has {} neighbours connecting to it, it needs exactly 2", x, y, connections),
+        }
+    }
 }
 
 /// Read an input file and return a Ok(Vec<String>) with one String per line
@@ -38,6 +53,7 @@ fn parse<T>(input_buffer: T) -> Result<Vec<String>, ParseError> where T: BufRead
     Ok(result)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Move {
     North,
     South,
@@ -45,6 +61,19 @@ enum Move {
     West,
 }
 
+impl Move {
+    const ALL: [Move; 4] = [Move::North, Move::South, Move::East, Move::West];
+
+    fn opposite(&self) -> Move {
+        match self {
+            Move::North => Move::South,
+            Move::South => Move::North,
+            Move::East => Move::West,
+            Move::West => Move::East,
+        }
+    }
+}
+
 fn go(direction: &Move, x: usize, y: usize) -> (usize, usize) {
     match direction {
         Move::North => (x, y-1),
@@ -75,6 +104,25 @@ enum Direction {
 }
 
 impl Direction {
+    /// Whether the pipe has an opening on the side the move goes through
+    fn connects(&self, side: &Move) -> bool {
+        matches!((self, side),
+            (Direction::NorthSouth, Move::North | Move::South)
+            | (Direction::EastWest, Move::East | Move::West)
+            | (Direction::NorthEast, Move::North | Move::East)
+            | (Direction::NorthWest, Move::North | Move::West)
+            | (Direction::SouthWest, Move::South | Move::West)
+            | (Direction::SouthEast, Move::South | Move::East))
+    }
+
+    /// The pipe with openings on these two sides, in either order
+    fn from_exits(a: &Move, b: &Move) -> Option<Direction> {
+        [Direction::NorthSouth, Direction::EastWest, Direction::NorthEast,
+            Direction::NorthWest, Direction::SouthWest, Direction::SouthEast]
+            .into_iter()
+            .find(|d| a != b && d.connects(a) && d.connects(b))
+    }
+
     fn next_move(&self, incoming_step: &Move) -> Option<Move> {
         match (self, incoming_step) {
             (Direction::NorthSouth, Move::North) => Some(Move::North), // I came in through the north, I should go north
@@ -178,6 +226,28 @@ impl Map {
         }
     }
 
+    /// The point one move away, None off the edge of the map
+    fn neighbour(&self, x: usize, y: usize, side: &Move) -> Option<Point> {
+        match side {
+            Move::North => self.get_point(x, y.checked_sub(1)?),
+            Move::South => self.get_point(x, y + 1),
+            Move::East => self.get_point(x + 1, y),
+            Move::West => self.get_point(x.checked_sub(1)?, y),
+        }
+    }
+
+    /// Work out what pipe S is from which neighbours have an opening facing it.
+    /// Returns the pipe and the two moves out of S, in North, South, East, West order.
+    fn start_pipe(&self, x: usize, y: usize) -> Result<(Direction, [Move; 2]), ParseError> {
+        let exits: Vec<Move> = Move::ALL.into_iter()
+            .filter(|m| self.neighbour(x, y, m).is_some_and(|p| p.direction.connects(&m.opposite())))
+            .collect();
+        match exits[..] {
+            [a, b] => Ok((Direction::from_exits(&a, &b).unwrap(), [a, b])),
+            _ => Err(ParseError::StartConnections { x, y, connections: exits.len() }),
+        }
+    }
+
     fn get_adjacent(&self, x: usize, y: usize) -> Vec<Option<Point>> {
         vec![
             self.get_point(x-1, y),
@@ -193,7 +263,14 @@ impl Map {
         ]
     }
 }
-fn main() -> Result<(), ParseError> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), ParseError> {
     let args = Args::parse();
 
     let input_file = File::open(args.input).unwrap();
@@ -220,21 +297,21 @@ fn main() -> Result<(), ParseError> {
     }
 
     let height = map.len();
-    let width = map.get(0).unwrap().len();
+    let width = map.first().unwrap().len();
 
     let mut map = Map { points: map, width, height };
 
-    assert!(cur_x.is_some());
-    assert!(cur_y.is_some());
-
-    let mut cur_x = cur_x.unwrap();
-    let mut cur_y = cur_y.unwrap();
+    let (mut cur_x, mut cur_y) = match (cur_x, cur_y) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err(ParseError::NoStart),
+    };
 
     println!("Starting x: {} y: {}", cur_x, cur_y);
+    let (start_pipe, exits) = map.start_pipe(cur_x, cur_y)?;
+    println!("Start pipe: {}", start_pipe);
 
     let mut step_count = 0;
-    // TODO this is a cheat based on me looking at the input
-    let mut step = Some(Move::North);
+    let mut step = Some(exits[0]);
     while let Some(s) = step {
         step_count += 1;
         (cur_x, cur_y) = go(&s, cur_x, cur_y);
@@ -314,3 +391,32 @@ fn main() -> Result<(), ParseError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, Map, Move, ParseError, Point};
+
+    fn map(text: &str) -> Map {
+        let points: Vec<Vec<Point>> = text.lines().enumerate().map(|(y, line)| {
+            line.chars().enumerate()
+                .map(|(x, c)| Point { x, y, direction: Direction::try_from(c).unwrap(), color: None })
+                .collect()
+        }).collect();
+        Map { width: points[0].len(), height: points.len(), points }
+    }
+
+    #[test]
+    fn test_start_pipe() {
+        let m = map("..F7.\n.FJ|.\nSJ.L7\n|F--J\nLJ...");
+        let (pipe, exits) = m.start_pipe(0, 2).unwrap();
+        assert_eq!(pipe.to_string(), "F");
+        assert_eq!(exits, [Move::South, Move::East]);
+
+        let m = map(".|.\n-S-\n.|.");
+        assert!(matches!(m.start_pipe(1, 1), Err(ParseError::StartConnections { x: 1, y: 1, connections: 4 })));
+
+        // Neighbours that don't face S don't count
+        let m = map(".-.\n|S|\n.-.");
+        assert!(matches!(m.start_pipe(1, 1), Err(ParseError::StartConnections { connections: 0, .. })));
+    }
+}
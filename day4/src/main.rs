@@ -1,16 +1,18 @@
-use std::{fs::File, io::{BufRead, BufReader}, collections::{HashSet, HashMap}, f32::consts::E};
+use std::{fs::File, io::{BufRead, BufReader}, collections::HashMap};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 use nom::{
     bytes::complete::tag,
-    character::complete::{u64, multispace0, multispace1},
-    combinator::{map, all_consuming},
-    multi::separated_list0,
-    sequence::{delimited, tuple, preceded, terminated},
+    character::complete::{u64, space0},
+    combinator::all_consuming,
+    multi::many1,
+    sequence::{delimited, tuple, preceded},
     IResult,
 };
 
+use serde::Serialize;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 /// Command line args
@@ -24,11 +26,82 @@ struct Args {
     /// Defaults to 1
     #[arg(short, long, default_value_t = 1)]
     part: u8,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show which earlier cards generated the copies of each card
+    Provenance {
+        /// Only report on this card
+        #[arg(short, long)]
+        card: Option<u64>,
+    },
+    /// Export the card-win dependency graph
+    Graph {
+        #[arg(short, long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GraphFormat {
+    Dot,
+    Json,
 }
 
 #[derive(Debug)]
 enum ParseError {
-    Error
+    Error,
+    /// The line isn't `Card <id>: <winners> | <numbers>`.
+    /// column is 1-based and points at where parsing stopped.
+    Syntax { line: usize, column: usize, expected: &'static str },
+    /// The card parsed but its numbers aren't valid
+    InvalidCard { line: usize, error: CardError },
+    /// A card id showed up more than once
+    DuplicateId { line: usize, id: u64 },
+    /// Card ids have to go up by one every line
+    NonConsecutiveId { line: usize, expected: u64, found: u64 },
+    /// Every card has to have the same amount of winning and held numbers as the first one
+    CountMismatch { line: usize, card: u64, found: (usize, usize), expected: (usize, usize) },
+    /// A card wins copies of cards past the last one
+    MissingWonCard { line: usize, card: u64, won: u64 },
+    /// provenance was asked about a card that isn't in the input
+    NoCard(u64),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "couldn't read input"),
+            Self::Syntax { line, column, expected } => write!(f, "line {}, column {}: expected {}", line, column, expected),
+            Self::InvalidCard { line, error } => write!(f, "line {}: {}", line, error),
+            Self::DuplicateId { line, id } => write!(f, "line {}: card {} already showed up", line, id),
+            Self::NonConsecutiveId { line, expected, found } => write!(f, "line {}: expected card {}, found card {}", line, expected, found),
+            Self::CountMismatch { line, card, found, expected } => write!(f, "line {}: card {} has {} winning and {} held numbers, expected {} and {}", line, card, found.0, found.1, expected.0, expected.1),
+            Self::MissingWonCard { line, card, won } => write!(f, "line {}: card {} wins a copy of card {}, which isn't in the input", line, card, won),
+            Self::NoCard(id) => write!(f, "there's no card {}", id),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum CardError {
+    /// A number showed up twice on the same side of a card
+    DuplicateNumber { card: u64, number: u64 },
+    /// A number didn't fit in a NumberSet
+    OutOfRange { card: u64, number: u64 },
+}
+
+impl std::fmt::Display for CardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateNumber { card, number } => write!(f, "card {} has {} more than once", card, number),
+            Self::OutOfRange { card, number } => write!(f, "card {} has {}, numbers must be < {}", card, number, MAX_NUMBER),
+        }
+    }
 }
 
 /// Read an input file and return a Ok(Vec<String>) with one String per line
@@ -45,57 +118,236 @@ fn parse<T>(input_buffer: T) -> Result<Vec<String>, ParseError> where T: BufRead
     Ok(result)
 }
 
+/// Largest number (exclusive) that can show up on a card
+const MAX_NUMBER: u64 = 100;
+
+/// A set of card numbers stored as a bitset.
+/// Bit n is set iff n is in the set, so numbers have to be < MAX_NUMBER.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct NumberSet(u128);
+
+impl NumberSet {
+    /// Build a set from a card's numbers.
+    /// Errors if a number is repeated or doesn't fit in the bitset.
+    fn from_numbers(card: u64, numbers: &[u64]) -> Result<Self, CardError> {
+        let mut set = NumberSet::default();
+        for &number in numbers {
+            if number >= MAX_NUMBER {
+                return Err(CardError::OutOfRange { card, number });
+            }
+            if set.contains(number) {
+                return Err(CardError::DuplicateNumber { card, number });
+            }
+            set.0 |= 1 << number;
+        }
+        Ok(set)
+    }
+
+    fn contains(&self, number: u64) -> bool {
+        number < MAX_NUMBER && self.0 & (1 << number) != 0
+    }
+
+    fn len(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    fn intersection(&self, other: &NumberSet) -> NumberSet {
+        NumberSet(self.0 & other.0)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Card {
     id: u64,
-    winners: Vec<u64>,
-    numbers: Vec<u64>
+    /// How many of our numbers are winners, computed once at parse time
+    matches: u32,
 }
 
 impl Card {
+    fn new(id: u64, winners: &[u64], numbers: &[u64]) -> Result<Self, CardError> {
+        let winners = NumberSet::from_numbers(id, winners)?;
+        let numbers = NumberSet::from_numbers(id, numbers)?;
+        let matches = winners.intersection(&numbers).len();
+        Ok(Card { id, matches })
+    }
+
     fn compute_score(&self) -> u32 {
-        let winners : HashSet<u64> = self.winners.clone().into_iter().collect();
-        let numbers : HashSet<u64> = self.numbers.clone().into_iter().collect();
-        let matches : u32 = winners.intersection(&numbers).collect::<Vec<&u64>>().len().try_into().unwrap();
-        if matches == 0 {
+        if self.matches == 0 {
             0
         } else {
-            2_u32.pow(matches-1)
+            2_u32.pow(self.matches-1)
         }
     }
 
+    /// The ids of the cards this card wins a copy of
     fn compute_copies(&self) -> Vec<u64> {
-        let winners : HashSet<u64> = self.winners.clone().into_iter().collect();
-        let numbers : HashSet<u64> = self.numbers.clone().into_iter().collect();
-        let matches : u32 = winners.intersection(&numbers).collect::<Vec<&u64>>().len().try_into().unwrap();
-        let mut retval = Vec::new();
-        for i in 1_u64..=matches.into() {
-            retval.push(self.id + i);
+        (1..=u64::from(self.matches)).map(|i| self.id + i).collect()
+    }
+}
+
+/// Copies of a card that were won by another card
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Source {
+    card: u64,
+    copies: u64,
+}
+
+/// Where all the copies of one card came from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Provenance {
+    card: u64,
+    matches: u32,
+    /// Total copies, including the original
+    copies: u64,
+    /// Earlier cards that won copies of this card, in card order
+    sources: Vec<Source>,
+}
+
+/// Work out the provenance of every card in card_index, ordered by card id.
+/// Each copy of a card wins one copy of each of the next `matches` cards,
+/// so a card's copies are 1 (the original) plus the copies of every card that wins it.
+/// Cards always win later cards, so going through them in order is enough.
+/// parse_cards already checked that every won card exists.
+fn build_provenance(card_index: &HashMap<u64, Card>) -> Vec<Provenance> {
+    let mut ids: Vec<u64> = card_index.keys().copied().collect();
+    ids.sort();
+
+    let mut sources: HashMap<u64, Vec<Source>> = HashMap::new();
+    let mut result = Vec::new();
+    for id in ids {
+        let card = card_index.get(&id).unwrap();
+        let card_sources = sources.remove(&id).unwrap_or_default();
+        let copies = 1 + card_sources.iter().map(|s| s.copies).sum::<u64>();
+        for won in card.compute_copies() {
+            sources.entry(won).or_default().push(Source { card: id, copies });
+        }
+        result.push(Provenance { card: id, matches: card.matches, copies, sources: card_sources });
+    }
+    result
+}
+
+/// Render the card-win graph as Graphviz DOT.
+/// Edges point from the winning card to the card it won, labelled with the copies won.
+fn to_dot(provenance: &[Provenance]) -> String {
+    let mut out = String::from("digraph day4 {\n");
+    for p in provenance {
+        out.push_str(&format!("    {} [label=\"Card {}\\n{} copies\"];\n", p.card, p.card, p.copies));
+    }
+    for p in provenance {
+        for s in &p.sources {
+            out.push_str(&format!("    {} -> {} [label=\"{}\"];\n", s.card, p.card, s.copies));
         }
-        retval
-    }
-}
-
-fn line_parser(s: &str) -> IResult<&str, Card> {
-    map(
-        tuple((
-            // Gets the Game ID
-            delimited(delimited(multispace0, tag("Card"), multispace0), terminated(u64, multispace0), terminated(tag(":"), multispace0)),
-            // Gets the Vec<Grab> from everything to the right of the 'Game 1: '
-            separated_list0(delimited(multispace0, tag("|"), multispace0),
-             separated_list0(multispace1, preceded(multispace0, u64)))
-        )),
-        |(id, winners)| {
-            Card { 
-                id, 
-                winners: winners.get(0).unwrap().clone(), 
-                numbers: winners.get(1).unwrap().clone()
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Render the card-win graph as JSON, one entry per card
+fn to_json(provenance: &[Provenance]) -> String {
+    serde_json::to_string_pretty(provenance).unwrap()
+}
+
+fn print_provenance(p: &Provenance) {
+    println!("Card {}: {} matches, {} copies", p.card, p.matches, p.copies);
+    println!("\t1 original");
+    for s in &p.sources {
+        println!("\t{} from card {}", s.copies, s.card);
+    }
+}
+
+/// A card as it shows up in the input: id, winning numbers, numbers we have
+type RawCard = (u64, Vec<u64>, Vec<u64>);
+
+/// One or more numbers, each with optional leading whitespace
+fn numbers_parser(s: &str) -> IResult<&str, Vec<u64>> {
+    many1(preceded(space0, u64))(s)
+}
+
+/// Run one stage of a card line's parser on what's left of the line.
+/// A failure turns into a ParseError::Syntax saying what that stage expected and where.
+fn stage<'a, O, F>(line: usize, text: &str, rest: &'a str, mut parser: F, expected: &'static str) -> Result<(&'a str, O), ParseError>
+where F: FnMut(&'a str) -> IResult<&'a str, O> {
+    match parser(rest) {
+        Ok(r) => Ok(r),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ParseError::Syntax { line, column: text.len() - e.input.len() + 1, expected })
+        },
+        Err(nom::Err::Incomplete(_)) => Err(ParseError::Error),
+    }
+}
+
+/// Parses a line like 'Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53'
+/// There has to be exactly one '|' with numbers on both sides of it.
+fn parse_line(line: usize, text: &str) -> Result<RawCard, ParseError> {
+    let (rest, id) = stage(line, text, text,
+        delimited(tuple((space0, tag("Card"), space0)), u64, tuple((space0, tag(":")))),
+        "'Card <id>:'")?;
+    let (rest, winners) = stage(line, text, rest, numbers_parser, "winning numbers")?;
+    let (rest, _) = stage(line, text, rest, tuple((space0, tag("|"))), "'|' after the winning numbers")?;
+    let (rest, numbers) = stage(line, text, rest, numbers_parser, "numbers after the '|'")?;
+    stage(line, text, rest, all_consuming(space0), "the end of the line after the numbers")?;
+    Ok((id, winners, numbers))
+}
+
+/// Parse every card, checking that the ids go 1 by 1 and that every card
+/// has the same amount of winning and held numbers, and that no card wins copies past the last one.
+/// Blank lines are skipped, line numbers in errors are 1-based.
+fn parse_cards(lines: &[String]) -> Result<Vec<Card>, ParseError> {
+    let mut cards: Vec<Card> = Vec::new();
+    let mut card_lines = Vec::new();
+    let mut expected_counts = None;
+    for (index, text) in lines.iter().enumerate() {
+        let line = index + 1;
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let (id, winners, numbers) = parse_line(line, text)?;
+
+        if let (Some(first), Some(last)) = (cards.first(), cards.last()) {
+            if (first.id..=last.id).contains(&id) {
+                return Err(ParseError::DuplicateId { line, id });
+            }
+            if id != last.id + 1 {
+                return Err(ParseError::NonConsecutiveId { line, expected: last.id + 1, found: id });
+            }
+        }
+
+        let found = (winners.len(), numbers.len());
+        match expected_counts {
+            None => expected_counts = Some(found),
+            Some(expected) if expected != found => {
+                return Err(ParseError::CountMismatch { line, card: id, found, expected });
+            },
+            Some(_) => ()
+        }
+
+        match Card::new(id, &winners, &numbers) {
+            Ok(card) => cards.push(card),
+            Err(error) => return Err(ParseError::InvalidCard { line, error }),
+        }
+        card_lines.push(line);
+    }
+
+    // Only now is the last id known, so only now can the won copies be checked
+    if let Some(last) = cards.last().map(|c| c.id) {
+        for (card, line) in cards.iter().zip(card_lines) {
+            if card.id + u64::from(card.matches) > last {
+                return Err(ParseError::MissingWonCard { line, card: card.id, won: last + 1 });
             }
         }
-    )(s)
+    }
+    Ok(cards)
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
 
-fn main() -> Result<(), ParseError> {
+fn run() -> Result<(), ParseError> {
     let args = Args::parse();
     
     let input_file = File::open(args.input).unwrap();
@@ -104,11 +356,8 @@ fn main() -> Result<(), ParseError> {
     let mut answer = 0;
     let mut pt2_pending : Vec<u64> = Vec::new();
     let mut card_index: HashMap<u64, Card> = HashMap::new();
-    for range in input_ranges {
-        let card = match all_consuming(line_parser)(&range) {
-            Ok(s) => s.1,
-            Err(e) => panic!("Parser problem! {:?}", e)
-        };
+    let cards = parse_cards(&input_ranges)?;
+    for card in cards {
         card_index.insert(card.id, card.clone());
 
         answer += card.compute_score();
@@ -116,6 +365,27 @@ fn main() -> Result<(), ParseError> {
         pt2_pending.push(card.id);
     }
 
+    match args.command {
+        Some(Command::Provenance { card }) => {
+            if let Some(id) = card.filter(|id| !card_index.contains_key(id)) {
+                return Err(ParseError::NoCard(id));
+            }
+            for p in build_provenance(&card_index).iter().filter(|p| card.is_none() || card == Some(p.card)) {
+                print_provenance(p);
+            }
+            return Ok(());
+        },
+        Some(Command::Graph { format }) => {
+            let provenance = build_provenance(&card_index);
+            match format {
+                GraphFormat::Dot => print!("{}", to_dot(&provenance)),
+                GraphFormat::Json => println!("{}", to_json(&provenance)),
+            }
+            return Ok(());
+        },
+        None => ()
+    }
+
     let mut pt2_processed = Vec::new();
     loop {
         let pending = pt2_pending.pop();
@@ -133,3 +403,62 @@ fn main() -> Result<(), ParseError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{Card, CardError, ParseError, build_provenance, parse_cards, Source};
+
+    #[test]
+    fn test_card_matches() {
+        let card = Card::new(1, &[41, 48, 83, 86, 17], &[83, 86, 6, 31, 17, 9, 48, 53]).unwrap();
+        assert_eq!(card.matches, 4);
+        assert_eq!(card.compute_score(), 8);
+        assert_eq!(card.compute_copies(), vec![2, 3, 4, 5]);
+
+        let card = Card::new(6, &[31, 18, 13, 56, 72], &[74, 77, 10, 23, 35, 67, 36, 11]).unwrap();
+        assert_eq!(card.compute_score(), 0);
+        assert!(card.compute_copies().is_empty());
+    }
+
+    #[test]
+    fn test_card_rejects_bad_numbers() {
+        assert!(matches!(Card::new(1, &[1, 2, 1], &[3]), Err(CardError::DuplicateNumber { card: 1, number: 1 })));
+        assert!(matches!(Card::new(2, &[1], &[100]), Err(CardError::OutOfRange { card: 2, number: 100 })));
+    }
+
+    #[test]
+    fn test_provenance() {
+        let cards = [
+            Card::new(1, &[1, 2], &[1, 2]).unwrap(),
+            Card::new(2, &[1, 2], &[1, 3]).unwrap(),
+            Card::new(3, &[1, 2], &[3, 4]).unwrap(),
+        ];
+        let card_index: HashMap<u64, Card> = cards.into_iter().map(|c| (c.id, c)).collect();
+        let provenance = build_provenance(&card_index);
+
+        assert_eq!(provenance.iter().map(|p| p.copies).collect::<Vec<_>>(), vec![1, 2, 4]);
+        assert_eq!(provenance[2].sources, vec![Source { card: 1, copies: 1 }, Source { card: 2, copies: 2 }]);
+    }
+
+    fn lines(input: &[&str]) -> Vec<String> {
+        input.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn test_parse_cards() {
+        let cards = parse_cards(&lines(&["Card 1: 41 48 | 83 48  6", "Card   2:  1  2 |  5  3 4", ""])).unwrap();
+        assert_eq!(cards.iter().map(|c| (c.id, c.matches)).collect::<Vec<_>>(), vec![(1, 1), (2, 0)]);
+
+        assert!(matches!(parse_cards(&lines(&["Card 1: 41 48 83"])), Err(ParseError::Syntax { line: 1, column: 17, expected: "'|' after the winning numbers" })));
+        assert!(matches!(parse_cards(&lines(&["Card 1: 41 | 48 | 83"])), Err(ParseError::Syntax { line: 1, column: 17, expected: "the end of the line after the numbers" })));
+        assert!(matches!(parse_cards(&lines(&["Crd 1: 41 | 48"])), Err(ParseError::Syntax { line: 1, column: 1, expected: "'Card <id>:'" })));
+        assert!(matches!(parse_cards(&lines(&["Card 1: 1 | 2", "Card 1: 1 | 2"])), Err(ParseError::DuplicateId { line: 2, id: 1 })));
+        assert!(matches!(parse_cards(&lines(&["Card 1: 1 | 2", "Card 3: 1 | 2"])), Err(ParseError::NonConsecutiveId { line: 2, expected: 2, found: 3 })));
+        assert!(matches!(parse_cards(&lines(&["Card 1: 1 | 2", "Card 2: 1 | 2 3"])), Err(ParseError::CountMismatch { line: 2, found: (1, 2), expected: (1, 1), .. })));
+        assert!(matches!(parse_cards(&lines(&["Card 1: 1 1 | 2"])), Err(ParseError::InvalidCard { line: 1, .. })));
+        assert!(matches!(parse_cards(&lines(&["Card 1: 1 2 | 2 1", "Card 2: 3 4 | 3 5"])), Err(ParseError::MissingWonCard { line: 1, card: 1, won: 3 })));
+        assert!(matches!(parse_cards(&lines(&["Card 1: 1 2 | 2 5", "Card 2: 3 4 | 3 5"])), Err(ParseError::MissingWonCard { line: 2, card: 2, won: 3 })));
+    }
+}
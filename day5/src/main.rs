@@ -1,13 +1,13 @@
-use std::{fs::File, io::{BufRead, BufReader, Read}, str, ops::Range, collections::{VecDeque, HashMap}, cmp::min};
+use std::{fs::File, io::{BufRead, BufReader}, ops::Range, collections::{HashMap, HashSet}, cmp::{min, max}};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 use nom::{
     bytes::complete::tag,
-    character::complete::{u64, alphanumeric1},
-    combinator::{map, all_consuming},
+    character::complete::{u64, alphanumeric1, space0, space1},
+    combinator::{map, map_opt, all_consuming},
     multi::separated_list1,
-    sequence::{tuple, separated_pair},
+    sequence::{delimited, tuple},
     IResult,
 };
 
@@ -24,11 +24,82 @@ struct Args {
     /// Defaults to 1
     #[arg(short, long, default_value_t = 1)]
     part: u8,
+
+    /// Also walk every single seed through the maps and check it
+    /// agrees with the interval answer. Slow on real inputs.
+    #[arg(long)]
+    brute_force: bool,
+
+    /// Category the seed numbers are in
+    #[arg(long, default_value = "seed")]
+    from: String,
+
+    /// Category to map them to
+    #[arg(long, default_value = "location")]
+    to: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the segments of the composed --from to --to map
+    Segments,
+    /// Look up each value going --from to --to, or the other way with --inverse
+    Lookup {
+        #[arg(long)]
+        inverse: bool,
+
+        #[arg(value_parser = parse_value)]
+        values: Vec<u64>,
+    },
+    /// Check the almanac for overlaps, gaps, empty ranges, overflow and lines that don't parse
+    Lint,
+}
+
+/// Almanac values, so anything a map can be looked up with, go up to u64::MAX - 1.
+/// Ranges are Range<u64> with an exclusive end, so u64::MAX itself can't be in one.
+fn parse_value(s: &str) -> Result<u64, String> {
+    match s.parse::<u64>() {
+        Ok(u64::MAX) => Err(format!("values have to be below {}", u64::MAX)),
+        Ok(v) => Ok(v),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
 #[derive(Debug)]
 enum ParseError {
-    Error
+    Error,
+    /// Part 2 reads the seeds as (start, length) pairs, so there has to be an even number of them
+    OddSeedCount(usize),
+    /// The blocks don't make a usable category graph
+    Graph(GraphError),
+    /// A line didn't parse. line and column are 1-based, column is where parsing stopped.
+    Syntax { line: usize, column: usize, expected: &'static str },
+    /// There's no 'seeds:' line
+    NoSeeds,
+    /// A part 2 seed range runs past u64::MAX
+    SeedOverflow { start: u64, length: u64 },
+}
+
+impl From<GraphError> for ParseError {
+    fn from(e: GraphError) -> Self {
+        ParseError::Graph(e)
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "couldn't read input"),
+            Self::OddSeedCount(count) => write!(f, "part 2 reads seeds as start/length pairs, but there are {} seed numbers", count),
+            Self::Graph(e) => write!(f, "{}", e),
+            Self::Syntax { line, column, expected } => write!(f, "line {}, column {}: expected {}", line, column, expected),
+            Self::NoSeeds => write!(f, "no seeds line"),
+            Self::SeedOverflow { start, length } => write!(f, "seed range starting at {} with length {} runs past {}", start, length, u64::MAX),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -48,6 +119,42 @@ impl Block {
 
         src
     }
+
+    /// Map whole intervals through the block.
+    /// Each interval gets split at the boundaries of our ranges, the pieces
+    /// inside a range get shifted to its destination and the rest map to themselves.
+    fn map_ranges(&self, srcs: &[Range<u64>]) -> Vec<Range<u64>> {
+        let mut unmapped = srcs.to_vec();
+        let mut mapped = vec![];
+        for range in &self.ranges {
+            let mut leftovers = vec![];
+            for src in unmapped {
+                let (dest, mut rest) = range.split(&src);
+                if let Some(dest) = dest {
+                    mapped.push(dest);
+                }
+                leftovers.append(&mut rest);
+            }
+            unmapped = leftovers;
+        }
+        mapped.append(&mut unmapped);
+        merge_ranges(mapped)
+    }
+}
+
+/// Sort the intervals and join the ones that overlap or touch,
+/// so the count stays small as we go through the blocks.
+fn merge_ranges(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.retain(|r| !r.is_empty());
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<u64>> = vec![];
+    for r in ranges {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = max(last.end, r.end),
+            _ => merged.push(r)
+        }
+    }
+    merged
 }
 
 #[derive(Debug, Clone)]
@@ -66,93 +173,575 @@ impl Ranges {
             false => None
         }
     }
+
+    /// Split src by our source range.
+    /// Returns the part that overlaps, already moved to the destination,
+    /// and the parts before and after it, which this range doesn't map.
+    fn split(&self, src: &Range<u64>) -> (Option<Range<u64>>, Vec<Range<u64>>) {
+        let start = max(src.start, self.source.start);
+        let end = min(src.end, self.source.end);
+        if start >= end {
+            return (None, vec![src.clone()]);
+        }
+
+        let mut rest = vec![];
+        if src.start < start {
+            rest.push(src.start..start);
+        }
+        if end < src.end {
+            rest.push(end..src.end);
+        }
+        let offset = start - self.source.start;
+        let dest = (self.destination.start + offset)..(self.destination.start + offset + (end - start));
+        (Some(dest), rest)
+    }
 }
 
-fn chunk_parser(s: &str) -> IResult<&str, Block> {
+/// Parses a block header like 'seed-to-soil map:' into (from, to)
+fn header_parser(s: &str) -> IResult<&str, (&str, &str)> {
     map(
         tuple((
-        alphanumeric1,
-        tag("-to-"),
-        alphanumeric1,
-        tag(" map:\n"),
-        separated_list1(tag("\n"),
-        parse_ranges)
-        ))
-    , |(a, _, b, _, c)| {
-        Block { from: String::from(a), to: String::from(b), ranges: c}
-    })(s)
+            space0,
+            alphanumeric1,
+            tag("-to-"),
+            alphanumeric1,
+            space1,
+            tag("map:"),
+            space0,
+        )),
+        |(_, from, _, to, _, _, _)| (from, to)
+    )(s)
 }
 
+/// Parses 'destination source length'.
+/// Fails if either range would run past u64::MAX, `day5 lint` says which one.
 fn parse_ranges(s: &str) -> IResult<&str, Ranges> {
-    map(
-    separated_list1(tag(" "), u64),
-    |v| {
-        assert_eq!(v.len(), 3);
-        let s_start = *v.get(1).unwrap();
-        let d_start = *v.get(0).unwrap();
-        let run = v.get(2).unwrap();
-        Ranges { source: s_start..(s_start+run), destination: d_start..(d_start+run) }
-    }
+    map_opt(
+        delimited(space0, tuple((u64, space1, u64, space1, u64)), space0),
+        |(d_start, _, s_start, _, run)| {
+            Some(Ranges { source: s_start..s_start.checked_add(run)?, destination: d_start..d_start.checked_add(run)? })
+        }
     )(s)
 }
 
-fn parse_seeds(s: &str) -> IResult<&str, Vec<(u64, u64)>> {
+/// Parses the seed numbers as they are, seed_ranges decides what they mean
+fn parse_seeds(s: &str) -> IResult<&str, Vec<u64>> {
     map(
         tuple((
-            tag("seeds: "),
-            separated_list1(tag(" "), separated_pair(u64, tag(" "), u64))
+            space0,
+            tag("seeds:"),
+            space0,
+            separated_list1(space1, u64),
+            space0,
         )),
-        |(_, v)| { v }
+        |(_, _, _, v, _)| { v }
     )(s)
 }
-fn parse<T>(input_buffer: T) -> Result<(Vec<(u64, u64)>, Vec<Block>), ParseError> where T: BufRead {
-    let mut reader = BufReader::new(input_buffer);
-    let mut block = vec![];
-    reader.read_to_end(&mut block).unwrap();
-    let file_contents = str::from_utf8(&block).unwrap();
-    let mut blocks : VecDeque<&str> = file_contents.split("\n\n").collect();
+/// One piece of a PiecewiseMap: every value in source gets offset added to it
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    source: Range<u64>,
+    offset: i128,
+}
 
-    let seeds: Vec<(u64, u64)> = match all_consuming(parse_seeds)(blocks.pop_front().unwrap()) {
-        Ok(s) => s.1,
-        Err(e) => panic!("Seed Parser Problem! {:?}", e)
-    };
-    let blocks = blocks.into_iter().map(|b| {
-        match chunk_parser(b) {
-            Ok(s) => s.1,
-            Err(e) => panic!("Parser problem! {:?}", e)
+impl Segment {
+    fn dest(&self) -> Range<u64> {
+        shift(self.source.start, self.offset)..shift(self.source.end, self.offset)
+    }
+}
+
+fn shift(value: u64, offset: i128) -> u64 {
+    u64::try_from(i128::from(value) + offset).unwrap()
+}
+
+impl std::fmt::Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dest = self.dest();
+        write!(f, "{}..{} -> {}..{} ({:+})", self.source.start, self.source.end, dest.start, dest.end, self.offset)
+    }
+}
+
+/// A piecewise-linear map made of sorted, non-overlapping segments.
+/// Values not covered by a segment have no mapping.
+#[derive(Debug, Clone, PartialEq)]
+struct PiecewiseMap {
+    segments: Vec<Segment>
+}
+
+impl PiecewiseMap {
+    /// The map that sends every value below u64::MAX to itself
+    fn identity() -> Self {
+        PiecewiseMap { segments: vec![Segment { source: 0..u64::MAX, offset: 0 }] }
+    }
+
+    /// Turn a block into a map covering all of u64 but u64::MAX, see parse_value.
+    /// The gaps between ranges map to themselves, same as Block::get_dest.
+    fn from_block(block: &Block) -> Self {
+        let mut segments = vec![];
+        let mut rest: Vec<Range<u64>> = std::iter::once(0..u64::MAX).collect();
+        for range in &block.ranges {
+            let offset = i128::from(range.destination.start) - i128::from(range.source.start);
+            let mut leftovers = vec![];
+            for r in rest {
+                let start = max(r.start, range.source.start);
+                let end = min(r.end, range.source.end);
+                if start < end {
+                    segments.push(Segment { source: start..end, offset });
+                    if r.start < start { leftovers.push(r.start..start); }
+                    if end < r.end { leftovers.push(end..r.end); }
+                } else {
+                    leftovers.push(r);
+                }
+            }
+            rest = leftovers;
+        }
+        segments.extend(rest.into_iter().map(|source| Segment { source, offset: 0 }));
+        segments.sort_by_key(|s| s.source.start);
+        PiecewiseMap { segments }
+    }
+
+    /// Apply self, then next, as a single map
+    fn then(&self, next: &PiecewiseMap) -> Self {
+        let mut segments = vec![];
+        for segment in &self.segments {
+            let dest = segment.dest();
+            let first = next.segments.partition_point(|s| s.source.end <= dest.start);
+            for n in next.segments[first..].iter().take_while(|s| s.source.start < dest.end) {
+                let start = max(dest.start, n.source.start);
+                let end = min(dest.end, n.source.end);
+                segments.push(Segment {
+                    source: shift(start, -segment.offset)..shift(end, -segment.offset),
+                    offset: segment.offset + n.offset,
+                });
+            }
+        }
+        segments.sort_by_key(|s| s.source.start);
+
+        // Join neighbours that ended up with the same offset
+        let mut merged: Vec<Segment> = vec![];
+        for s in segments {
+            match merged.last_mut() {
+                Some(last) if last.source.end == s.source.start && last.offset == s.offset => last.source.end = s.source.end,
+                _ => merged.push(s)
+            }
+        }
+        PiecewiseMap { segments: merged }
+    }
+
+    /// Look a value up with a binary search over the segments
+    fn get(&self, value: u64) -> Option<u64> {
+        let index = self.segments.partition_point(|s| s.source.end <= value);
+        match self.segments.get(index) {
+            Some(s) if s.source.contains(&value) => Some(shift(value, s.offset)),
+            _ => None
+        }
+    }
+
+    /// The map going the other way.
+    /// None if two values map to the same place, since then there's no single answer.
+    fn inverse(&self) -> Option<Self> {
+        let mut segments: Vec<Segment> = self.segments.iter()
+            .map(|s| Segment { source: s.dest(), offset: -s.offset })
+            .collect();
+        segments.sort_by_key(|s| s.source.start);
+        if segments.windows(2).any(|w| w[0].source.end > w[1].source.start) {
+            return None;
+        }
+        Some(PiecewiseMap { segments })
+    }
+}
+
+#[derive(Debug)]
+enum GraphError {
+    /// There's more than one from-to-to map
+    DuplicateMap { from: String, to: String },
+    /// Following the maps from a category gets back to it
+    Cycle(Vec<String>),
+    /// There's no way to get from one category to the other
+    NoPath { from: String, to: String },
+    /// More than one way to get from one category to the other, so no single answer
+    AmbiguousPath { from: String, to: String, paths: Vec<Vec<String>> },
+    /// The composed map sends more than one value to the same place, so it can't be inverted
+    NotOneToOne { from: String, to: String },
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateMap { from, to } => write!(f, "there is more than one {}-to-{} map", from, to),
+            Self::Cycle(categories) => write!(f, "the maps go around in a cycle: {}", categories.join(" -> ")),
+            Self::NoPath { from, to } => write!(f, "no maps lead from {} to {}", from, to),
+            Self::AmbiguousPath { from, to, paths } => {
+                write!(f, "more than one way to get from {} to {}:", from, to)?;
+                for path in paths {
+                    write!(f, " [{}]", path.join(" -> "))?;
+                }
+                Ok(())
+            },
+            Self::NotOneToOne { from, to } => write!(f, "the {} to {} map isn't one to one, so it can't be inverted", from, to),
+        }
+    }
+}
+
+/// The almanac as a directed graph of categories, with a Block on each edge.
+/// A category can map to any number of others, but there can't be cycles.
+struct CategoryGraph {
+    edges: HashMap<String, Vec<Block>>
+}
+
+impl CategoryGraph {
+    fn new(blocks: Vec<Block>) -> Result<Self, GraphError> {
+        let mut edges: HashMap<String, Vec<Block>> = HashMap::new();
+        for block in blocks {
+            let out = edges.entry(block.from.clone()).or_default();
+            if out.iter().any(|b| b.to == block.to) {
+                return Err(GraphError::DuplicateMap { from: block.from, to: block.to });
+            }
+            out.push(block);
+        }
+        let graph = CategoryGraph { edges };
+        graph.check_cycles()?;
+        Ok(graph)
+    }
+
+    /// Depth first search from every category, erroring on the first back edge
+    fn check_cycles(&self) -> Result<(), GraphError> {
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut starts: Vec<&str> = self.edges.keys().map(|k| k.as_str()).collect();
+        starts.sort();
+        for start in starts {
+            let mut stack = vec![];
+            self.visit(start, &mut stack, &mut done)?;
+        }
+        Ok(())
+    }
+
+    fn visit<'a>(&'a self, category: &'a str, stack: &mut Vec<&'a str>, done: &mut HashSet<&'a str>) -> Result<(), GraphError> {
+        if let Some(pos) = stack.iter().position(|c| *c == category) {
+            let mut cycle: Vec<String> = stack[pos..].iter().map(|c| String::from(*c)).collect();
+            cycle.push(String::from(category));
+            return Err(GraphError::Cycle(cycle));
+        }
+        if done.contains(category) {
+            return Ok(());
+        }
+        stack.push(category);
+        for block in self.edges.get(category).into_iter().flatten() {
+            self.visit(&block.to, stack, done)?;
+        }
+        stack.pop();
+        done.insert(category);
+        Ok(())
+    }
+
+    /// Every way of getting from `from` to `to`, as the blocks to go through
+    fn all_paths<'a>(&'a self, from: &str, to: &str, path: &mut Vec<&'a Block>, found: &mut Vec<Vec<&'a Block>>) {
+        if from == to {
+            found.push(path.clone());
+            return;
         }
-    }).collect();
-    Ok((seeds, blocks))
+        for block in self.edges.get(from).into_iter().flatten() {
+            path.push(block);
+            self.all_paths(&block.to, to, path, found);
+            path.pop();
+        }
+    }
+
+    /// The blocks leading from `from` to `to`.
+    /// There has to be exactly one way to get there.
+    fn path(&self, from: &str, to: &str) -> Result<Vec<&Block>, GraphError> {
+        let mut found = vec![];
+        self.all_paths(from, to, &mut vec![], &mut found);
+        match found.len() {
+            0 => Err(GraphError::NoPath { from: String::from(from), to: String::from(to) }),
+            1 => Ok(found.pop().unwrap()),
+            _ => Err(GraphError::AmbiguousPath {
+                from: String::from(from),
+                to: String::from(to),
+                paths: found.iter().map(|p| {
+                    let mut names = vec![String::from(from)];
+                    names.extend(p.iter().map(|b| b.to.clone()));
+                    names
+                }).collect()
+            })
+        }
+    }
+
+    /// Compose the blocks from `from` to `to` into a single map
+    fn compose(&self, from: &str, to: &str) -> Result<PiecewiseMap, GraphError> {
+        Ok(self.path(from, to)?.iter().fold(PiecewiseMap::identity(), |composed, block| {
+            composed.then(&PiecewiseMap::from_block(block))
+        }))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum LintKind {
+    /// The line isn't a header, a seeds line or three numbers
+    Unparseable(String),
+    /// A range with length 0, it never maps anything
+    ZeroLength,
+    /// start + length doesn't fit in a u64
+    Overflow,
+    /// Our source range overlaps the one on another line, so which one wins depends on order
+    Overlap { other_line: usize, range: Range<u64> },
+    /// Nothing maps these values, so they go through unchanged
+    Gap(Range<u64>),
+}
+
+/// Something `day5 lint` found, with the 1-based line and the block it's in
+#[derive(Debug, PartialEq)]
+struct LintIssue {
+    line: usize,
+    block: Option<String>,
+    kind: LintKind,
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(block) = &self.block {
+            write!(f, " [{}]", block)?;
+        }
+        match &self.kind {
+            LintKind::Unparseable(text) => write!(f, ": can't parse {:?}", text),
+            LintKind::ZeroLength => write!(f, ": range has length 0"),
+            LintKind::Overflow => write!(f, ": range runs past u64::MAX"),
+            LintKind::Overlap { other_line, range } => write!(f, ": source overlaps line {} on {}..{}", other_line, range.start, range.end),
+            LintKind::Gap(range) => write!(f, ": gap in sources {}..{}", range.start, range.end),
+        }
+    }
+}
+
+/// Go through the almanac line by line, without the real parser, so
+/// problems are reported with line numbers instead of stopping at the first one.
+fn lint(contents: &str) -> Vec<LintIssue> {
+    let mut issues = vec![];
+    let mut block: Option<String> = None;
+    // (line, source range) for every range in the current block
+    let mut sources: Vec<(usize, Range<u64>)> = vec![];
+
+    for (index, text) in contents.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if text.starts_with("seeds:") {
+            if all_consuming(parse_seeds)(text).is_err() {
+                issues.push(LintIssue { line, block: None, kind: LintKind::Unparseable(String::from(text)) });
+            }
+            continue;
+        }
+        if let Ok((_, (from, to))) = all_consuming(header_parser)(text) {
+            lint_sources(&block, &mut sources, &mut issues);
+            block = Some(format!("{}-to-{}", from, to));
+            continue;
+        }
+
+        let numbers: Vec<Option<u64>> = text.split_whitespace().map(|n| n.parse().ok()).collect();
+        let (d_start, s_start, run) = match numbers[..] {
+            [Some(d), Some(s), Some(r)] if block.is_some() => (d, s, r),
+            _ => {
+                issues.push(LintIssue { line, block: block.clone(), kind: LintKind::Unparseable(String::from(text)) });
+                continue;
+            }
+        };
+        if run == 0 {
+            issues.push(LintIssue { line, block: block.clone(), kind: LintKind::ZeroLength });
+            continue;
+        }
+        match (s_start.checked_add(run), d_start.checked_add(run)) {
+            (Some(s_end), Some(_)) => sources.push((line, s_start..s_end)),
+            _ => issues.push(LintIssue { line, block: block.clone(), kind: LintKind::Overflow }),
+        }
+    }
+    lint_sources(&block, &mut sources, &mut issues);
+    issues.sort_by_key(|i| i.line);
+    issues
+}
+
+/// Check the source ranges of one block for overlaps and gaps, then clear them
+fn lint_sources(block: &Option<String>, sources: &mut Vec<(usize, Range<u64>)>, issues: &mut Vec<LintIssue>) {
+    sources.sort_by_key(|(_, r)| r.start);
+    // The range reaching furthest so far, since it can overlap more than its neighbour
+    let mut furthest: Option<&(usize, Range<u64>)> = None;
+    for current in sources.iter() {
+        if let Some((other_line, other)) = furthest {
+            let (line, range) = current;
+            if range.start < other.end {
+                issues.push(LintIssue {
+                    line: *line,
+                    block: block.clone(),
+                    kind: LintKind::Overlap { other_line: *other_line, range: range.start..min(range.end, other.end) },
+                });
+            } else if range.start > other.end {
+                issues.push(LintIssue { line: *line, block: block.clone(), kind: LintKind::Gap(other.end..range.start) });
+            }
+        }
+        if furthest.is_none_or(|(_, f)| current.1.end > f.end) {
+            furthest = Some(current);
+        }
+    }
+    sources.clear();
+}
+
+/// Turn the seed numbers into (start, length) pairs.
+/// Part 1: every number is a seed of its own.
+/// Part 2: the numbers are start/length pairs.
+fn seed_ranges(seeds: &[u64], part: u8) -> Result<Vec<(u64, u64)>, ParseError> {
+    match part {
+        1 => seeds.iter().map(|s| match s.checked_add(1) {
+            Some(_) => Ok((*s, 1)),
+            None => Err(ParseError::SeedOverflow { start: *s, length: 1 }),
+        }).collect(),
+        2 => {
+            if !seeds.len().is_multiple_of(2) {
+                return Err(ParseError::OddSeedCount(seeds.len()));
+            }
+            seeds.chunks(2).map(|c| match c[0].checked_add(c[1]) {
+                Some(_) => Ok((c[0], c[1])),
+                None => Err(ParseError::SeedOverflow { start: c[0], length: c[1] }),
+            }).collect()
+        },
+        _ => panic!("Unknown part")
+    }
+}
+
+/// Run parser over a whole line, turning a failure into a ParseError::Syntax
+/// that says where on the line it went wrong.
+fn parse_line<'a, O, F>(line: usize, text: &'a str, parser: F, expected: &'static str) -> Result<O, ParseError>
+where F: FnMut(&'a str) -> IResult<&'a str, O> {
+    match all_consuming(parser)(text) {
+        Ok((_, v)) => Ok(v),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ParseError::Syntax { line, column: text.len() - e.input.len() + 1, expected })
+        },
+        Err(nom::Err::Incomplete(_)) => Err(ParseError::Error)
+    }
+}
+
+/// Read the almanac: a seeds line, then blocks of a header followed by ranges.
+/// Goes line by line, so CRLF line endings, blank lines and any amount of
+/// spaces or tabs between things are fine.
+fn parse<T>(input_buffer: T) -> Result<(Vec<u64>, Vec<Block>), ParseError> where T: BufRead {
+    let mut seeds = None;
+    let mut blocks: Vec<Block> = vec![];
+    for (index, text) in BufReader::new(input_buffer).lines().enumerate() {
+        let line = index + 1;
+        let text = match text {
+            Ok(s) => s,
+            Err(_) => return Err(ParseError::Error)
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        if seeds.is_none() {
+            seeds = Some(parse_line(line, &text, parse_seeds, "'seeds:' followed by numbers")?);
+            continue;
+        }
+
+        // Lines of numbers belong to the last block, anything else has to be a new block
+        let starts_with_digit = text.trim_start().starts_with(|c: char| c.is_ascii_digit());
+        match blocks.last_mut() {
+            Some(block) if starts_with_digit => {
+                let range = parse_line(line, &text, parse_ranges, "destination, source and length, not running past u64::MAX")?;
+                block.ranges.push(range);
+            },
+            _ => {
+                let (from, to) = parse_line(line, &text, header_parser, "a header like 'seed-to-soil map:'")?;
+                blocks.push(Block { from: String::from(from), to: String::from(to), ranges: vec![] });
+            }
+        }
+    }
+
+    match seeds {
+        Some(seeds) => Ok((seeds, blocks)),
+        None => Err(ParseError::NoSeeds)
+    }
 }
 
-fn main() -> Result<(), ParseError> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), ParseError> {
     let args = Args::parse();
 
-    let input_file = File::open(args.input).unwrap();
+    if let Some(Command::Lint) = args.command {
+        let contents = std::fs::read_to_string(&args.input).map_err(|_| ParseError::Error)?;
+        let issues = lint(&contents);
+        for issue in &issues {
+            println!("{}", issue);
+        }
+        println!("{} issues", issues.len());
+        return Ok(());
+    }
+
+    let input_file = File::open(&args.input).unwrap();
     let (seeds, blocks) = parse(BufReader::new(input_file))?;
+    let seeds = seed_ranges(&seeds, args.part)?;
 
-    println!("seeds: {:?}", seeds);
+    let graph = CategoryGraph::new(blocks)?;
+    let (from, to) = (args.from.as_str(), args.to.as_str());
 
-    let mut src2dst : HashMap<String, Block> = HashMap::new();
-    for block in blocks {
-        src2dst.insert(block.from.clone(), block);
+    match args.command {
+        Some(Command::Segments) => {
+            for segment in graph.compose(from, to)?.segments {
+                println!("{}", segment);
+            }
+            return Ok(());
+        },
+        Some(Command::Lookup { inverse, values }) => {
+            let composed = graph.compose(from, to)?;
+            let (composed, from, to) = match inverse {
+                false => (composed, from, to),
+                true => match composed.inverse() {
+                    Some(inverse) => (inverse, to, from),
+                    None => return Err(GraphError::NotOneToOne { from: String::from(from), to: String::from(to) }.into()),
+                },
+            };
+            for value in values {
+                match composed.get(value) {
+                    Some(v) => println!("{} {} -> {} {}", from, value, to, v),
+                    None => println!("{} {} -> no {}", from, value, to),
+                }
+            }
+            return Ok(());
+        },
+        Some(Command::Lint) | None => ()
     }
 
-    let mut minv: u64 = u64::MAX;
+    println!("seeds: {:?}", seeds);
 
-    for (i, j) in seeds {
-        //println!("i: {}, j: {}", i, j);
-        for i in i..(i+j) {
-        let mut src = String::from("seed");
-        let mut value = i;
-        while let Some(dest) = src2dst.get(&src) {
-            value = dest.get_dest(value);
-            src = dest.to.clone();
-        }
-        assert_eq!(src, "location");
-        //println!("seed: {}, location: {}", i, value);
-        minv = min(minv, value);
+    let path = graph.path(from, to)?;
+    let mut intervals: Vec<Range<u64>> = merge_ranges(seeds.iter().map(|(start, len)| *start..(start + len)).collect());
+    for block in &path {
+        intervals = block.map_ranges(&intervals);
     }
+    println!("{} intervals: {}", to, intervals.len());
+    let minv = match intervals.first() {
+        Some(r) => r.start,
+        None => {
+            println!("No seeds to map");
+            return Ok(());
+        }
+    };
+
+    if args.brute_force {
+        let mut brute_minv: u64 = u64::MAX;
+        for (i, j) in seeds {
+            for i in i..(i+j) {
+                let value = path.iter().fold(i, |value, block| block.get_dest(value));
+                brute_minv = min(brute_minv, value);
+            }
+        }
+        println!("Brute force answer: {}", brute_minv);
+        assert_eq!(minv, brute_minv);
     }
 
     // Part 1 answer for me was 457535844
@@ -160,3 +749,125 @@ fn main() -> Result<(), ParseError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Block, Ranges, PiecewiseMap, ParseError, seed_ranges, CategoryGraph, GraphError, lint, LintIssue, LintKind, parse};
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_map_ranges() {
+        // seed-to-soil from the sample
+        let block = Block {
+            from: String::from("seed"),
+            to: String::from("soil"),
+            ranges: vec![
+                Ranges { source: 98..100, destination: 50..52 },
+                Ranges { source: 50..98, destination: 52..100 },
+            ]
+        };
+
+        assert_eq!(block.map_ranges(&[79..93]), vec![81..95]);
+        assert_eq!(block.map_ranges(&[40..60]), vec![40..50, 52..62]);
+        assert_eq!(block.map_ranges(&[97..101]), vec![50..52, 99..101]);
+
+        let mapped = block.map_ranges(&[0..120]);
+        for seed in 0..120 {
+            let dest = block.get_dest(seed);
+            assert!(mapped.iter().any(|r| r.contains(&dest)));
+        }
+        assert_eq!(mapped.iter().map(|r| r.end - r.start).sum::<u64>(), 120);
+    }
+
+    #[test]
+    fn test_compose() {
+        let seed_to_soil = Block {
+            from: String::from("seed"),
+            to: String::from("soil"),
+            ranges: vec![
+                Ranges { source: 98..100, destination: 50..52 },
+                Ranges { source: 50..98, destination: 52..100 },
+            ]
+        };
+        let soil_to_fertilizer = Block {
+            from: String::from("soil"),
+            to: String::from("fertilizer"),
+            ranges: vec![
+                Ranges { source: 15..52, destination: 0..37 },
+                Ranges { source: 52..54, destination: 37..39 },
+                Ranges { source: 0..15, destination: 39..54 },
+            ]
+        };
+
+        let composed = PiecewiseMap::from_block(&seed_to_soil).then(&PiecewiseMap::from_block(&soil_to_fertilizer));
+        let inverse = composed.inverse().unwrap();
+        for seed in 0..200 {
+            let expected = soil_to_fertilizer.get_dest(seed_to_soil.get_dest(seed));
+            assert_eq!(composed.get(seed), Some(expected));
+            assert_eq!(inverse.get(expected), Some(seed));
+        }
+    }
+
+    #[test]
+    fn test_seed_ranges() {
+        assert_eq!(seed_ranges(&[79, 14, 55, 13], 1).unwrap(), vec![(79, 1), (14, 1), (55, 1), (13, 1)]);
+        assert_eq!(seed_ranges(&[79, 14, 55, 13], 2).unwrap(), vec![(79, 14), (55, 13)]);
+        assert_eq!(seed_ranges(&[79, 14, 55], 1).unwrap().len(), 3);
+        assert!(matches!(seed_ranges(&[79, 14, 55], 2), Err(ParseError::OddSeedCount(3))));
+        assert!(matches!(seed_ranges(&[u64::MAX - 5, 10], 2), Err(ParseError::SeedOverflow { length: 10, .. })));
+        assert!(seed_ranges(&[u64::MAX - 5, 5], 2).is_ok());
+        assert!(matches!(seed_ranges(&[u64::MAX], 1), Err(ParseError::SeedOverflow { length: 1, .. })));
+    }
+
+    fn block(from: &str, to: &str) -> Block {
+        Block { from: String::from(from), to: String::from(to), ranges: vec![Ranges { source: 0..10, destination: 10..20 }] }
+    }
+
+    #[test]
+    fn test_category_graph() {
+        let graph = CategoryGraph::new(vec![block("seed", "soil"), block("soil", "water"), block("soil", "light"), block("light", "location")]).unwrap();
+        assert_eq!(graph.path("seed", "location").unwrap().len(), 3);
+        assert_eq!(graph.path("soil", "water").unwrap().len(), 1);
+        assert_eq!(graph.compose("seed", "light").unwrap().get(5), Some(15));
+        assert!(matches!(graph.path("water", "location"), Err(GraphError::NoPath { .. })));
+
+        let graph = CategoryGraph::new(vec![block("a", "b"), block("b", "d"), block("a", "c"), block("c", "d")]).unwrap();
+        assert!(matches!(graph.path("a", "d"), Err(GraphError::AmbiguousPath { .. })));
+
+        assert!(matches!(CategoryGraph::new(vec![block("a", "b"), block("a", "b")]), Err(GraphError::DuplicateMap { .. })));
+        match CategoryGraph::new(vec![block("a", "b"), block("b", "c"), block("c", "a")]) {
+            Err(GraphError::Cycle(cycle)) => assert_eq!(cycle, vec!["a", "b", "c", "a"]),
+            _ => panic!("Expected a cycle")
+        }
+    }
+
+    #[test]
+    fn test_lint() {
+        let almanac = "seeds: 1 2\n\na-to-b map:\n50 98 2\n52 50 48\n0 40 0\n1 2 3 4\n\nb-to-c map:\n0 10 10\n5 15 10\n0 18446744073709551615 2\n0 30 5\n";
+        let issue = |line: usize, block: &str, kind: LintKind| LintIssue { line, block: Some(String::from(block)), kind };
+        assert_eq!(lint(almanac), vec![
+            issue(6, "a-to-b", LintKind::ZeroLength),
+            issue(7, "a-to-b", LintKind::Unparseable(String::from("1 2 3 4"))),
+            issue(11, "b-to-c", LintKind::Overlap { other_line: 10, range: 15..20 }),
+            issue(12, "b-to-c", LintKind::Overflow),
+            issue(13, "b-to-c", LintKind::Gap(25..30)),
+        ]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let almanac = "\r\n seeds:  79\t14 55 13  \r\n\r\n\r\nseed-to-soil   map:\r\n50  98 2 \r\n\r\n52 50\t48\r\nsoil-to-water map:\n0 15 37\n\n\n";
+        let (seeds, blocks) = parse(almanac.as_bytes()).unwrap();
+        assert_eq!(seeds, vec![79, 14, 55, 13]);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!((blocks[0].from.as_str(), blocks[0].to.as_str()), ("seed", "soil"));
+        assert_eq!(blocks[0].ranges.len(), 2);
+        assert_eq!(blocks[0].ranges[1].source, 50..98);
+        assert_eq!(blocks[1].ranges[0].destination, 0..37);
+
+        assert!(matches!(parse("seeds: 1 2\nseed-to-soil map:\n1 2 x\n".as_bytes()), Err(ParseError::Syntax { line: 3, column: 5, .. })));
+        assert!(matches!(parse("seeds: 1 2\nseed-to-soil\n".as_bytes()), Err(ParseError::Syntax { line: 2, column: 13, .. })));
+        assert!(matches!(parse("seeds: 1 2\n1 2 3\n".as_bytes()), Err(ParseError::Syntax { line: 2, column: 2, .. })));
+        assert!(matches!(parse("\n\n".as_bytes()), Err(ParseError::NoSeeds)));
+    }
+}
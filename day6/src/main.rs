@@ -2,14 +2,16 @@ use std::{fs::File, io::{BufRead, BufReader}};
 
 use nom::{
     bytes::complete::tag,
-    character::complete::{u64, alphanumeric1, multispace1},
+    character::complete::{digit1, multispace1},
     combinator::{map, all_consuming},
     multi::separated_list1,
-    sequence::{delimited, tuple, separated_pair},
+    sequence::tuple,
     IResult,
 };
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use serde::Serialize;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -24,11 +26,54 @@ struct Args {
     /// Defaults to 1
     #[arg(short, long, default_value_t = 1)]
     part: u8,
+
+    /// Also count by trying every hold time and check it agrees with the closed form
+    #[arg(long)]
+    verify: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the best hold time, winning window and sensitivity to the record for each race
+    Explore {
+        #[arg(short, long, value_enum, default_value_t = ExploreFormat::Text)]
+        format: ExploreFormat,
+
+        /// Columns in the distance chart
+        #[arg(long, default_value_t = 60)]
+        width: usize,
+
+        /// Rows in the distance chart
+        #[arg(long, default_value_t = 12)]
+        height: usize,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExploreFormat {
+    /// Summary, sensitivity table and ASCII chart
+    Text,
+    Csv,
+    Json,
 }
 
 #[derive(Debug)]
 enum ParseError {
-    Error
+    Error,
+    /// A number doesn't fit in a u128, even after promoting it from u64
+    Overflow { label: &'static str, digits: String },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "couldn't read input"),
+            Self::Overflow { label, digits } => write!(f, "{} {} doesn't fit in a u128", label, digits),
+        }
+    }
 }
 
 /// Read an input file and return a Ok(Vec<String>) with one String per line
@@ -45,66 +90,279 @@ fn parse<T>(input_buffer: T) -> Result<Vec<String>, ParseError> where T: BufRead
     Ok(result)
 }
 
-fn time_parser(s: &str) -> IResult<&str, Vec<u64>> {
+/// Parses the digit groups after the label, leaving it to race_values
+/// to decide if they're separate races or one kerned number
+fn groups_parser<'a>(label: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<&'a str>> {
     map(
         tuple((
-            tag("Time:"), 
-            multispace1, 
-            separated_list1(multispace1, u64)
+            tag(label),
+            multispace1,
+            separated_list1(multispace1, digit1)
         )),
-        |(_, _, vals)| vals )(s)
+        |(_, _, vals)| vals )
 }
 
-fn distance_parser(s: &str) -> IResult<&str, Vec<u64>> {
-    map(
-        tuple((
-            tag("Distance:"), 
-            multispace1, 
-            separated_list1(multispace1, u64)
-        )),
-        |(_, _, vals)| vals )(s)
+fn time_parser(s: &str) -> IResult<&str, Vec<&str>> {
+    groups_parser("Time:")(s)
+}
+
+fn distance_parser(s: &str) -> IResult<&str, Vec<&str>> {
+    groups_parser("Distance:")(s)
+}
+
+/// Turn the digit groups of a line into numbers.
+/// Part 1: every group is a race of its own.
+/// Part 2: the spaces are bad kerning, so all the digits make up one race.
+/// Values are u128 so part 2's long numbers don't overflow a u64.
+fn race_values(label: &'static str, groups: &[&str], part: u8) -> Result<Vec<u128>, ParseError> {
+    let numbers = match part {
+        1 => groups.iter().map(|g| String::from(*g)).collect(),
+        2 => vec![groups.concat()],
+        _ => panic!("Unknown part")
+    };
+    numbers.into_iter().map(|digits| {
+        digits.parse::<u128>().map_err(|_| ParseError::Overflow { label, digits })
+    }).collect()
+}
+
+/// Does holding the button for `hold` out of `time` go further than `record`?
+/// A product too big for a u128 is definitely further.
+fn beats(hold: u128, time: u128, record: u128) -> bool {
+    match hold.checked_mul(time - hold) {
+        Some(distance) => distance > record,
+        None => true
+    }
+}
+
+/// The first and last hold times that beat the record, without trying them all.
+/// Holding for h goes h * (time - h), which beats the record between the roots of
+/// h^2 - time*h + record = 0, so (time +- sqrt(time^2 - 4*record)) / 2.
+/// The integer square root only gets us close to the first winning hold,
+/// so step to the exact one, which also takes care of holds that only tie the record.
+/// The winning holds are symmetric around time / 2.
+fn winning_window(time: u128, record: u128) -> Option<(u128, u128)> {
+    let mut lo = match time.checked_mul(time) {
+        Some(square) => {
+            let disc = match square.checked_sub(record.saturating_mul(4)) {
+                Some(d) if d > 0 => d,
+                _ => return None
+            };
+            (time - disc.isqrt()) / 2
+        },
+        // time^2 doesn't fit, so binary search for the first winning hold instead
+        None => {
+            let (mut lo, mut hi) = (0, time / 2);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if beats(mid, time, record) { hi = mid } else { lo = mid + 1 }
+            }
+            lo
+        }
+    };
+
+    while lo <= time / 2 && !beats(lo, time, record) {
+        lo += 1;
+    }
+    while lo > 0 && beats(lo - 1, time, record) {
+        lo -= 1;
+    }
+    if lo > time / 2 {
+        return None;
+    }
+    Some((lo, time - lo))
+}
+
+/// Count the hold times that beat the record, see winning_window
+fn find_record_breaking_count(time: u128, record: u128) -> u128 {
+    match winning_window(time, record) {
+        Some((lo, hi)) => hi - lo + 1,
+        None => 0
+    }
+}
+
+/// Count the hold times that beat the record by trying every one of them
+fn find_record_breaking_count_loop(time: u128, record: u128) -> u128 {
+    (0..=time).filter(|hold| beats(*hold, time, record)).count() as u128
+}
+
+/// Percentages of the real record to try in the sensitivity table
+const SENSITIVITY_PERCENTS: [u128; 7] = [50, 80, 90, 100, 110, 120, 150];
+
+/// How many ways there are to win if the record was something else
+#[derive(Debug, Serialize)]
+struct Sensitivity {
+    percent: u128,
+    /// The record scaled by percent, u128::MAX if that doesn't fit
+    record: u128,
+    ways: u128,
+}
+
+/// Everything explore shows about a race
+#[derive(Debug, Serialize)]
+struct RaceAnalysis {
+    race: usize,
+    time: u128,
+    record: u128,
+    /// Hold time that goes furthest, time / 2 (time / 2 + 1 ties it for odd times)
+    best_hold: u128,
+    /// None if it doesn't fit in a u128
+    best_distance: Option<u128>,
+    /// How far past the record the best hold goes, 0 if it doesn't beat it.
+    /// None when best_distance is
+    margin: Option<u128>,
+    /// First and last winning hold times
+    window: Option<(u128, u128)>,
+    ways: u128,
+    sensitivity: Vec<Sensitivity>,
+}
+
+impl RaceAnalysis {
+    fn new(race: usize, time: u128, record: u128) -> Self {
+        let best_hold = time / 2;
+        let best_distance = best_hold.checked_mul(time - best_hold);
+        let window = winning_window(time, record);
+        let sensitivity = SENSITIVITY_PERCENTS.iter().map(|percent| {
+            // record * percent / 100 without the multiply overflowing first, u128::MAX if the result doesn't fit
+            let record = (record / 100).checked_mul(*percent)
+                .and_then(|r| r.checked_add(record % 100 * percent / 100))
+                .unwrap_or(u128::MAX);
+            Sensitivity { percent: *percent, record, ways: find_record_breaking_count(time, record) }
+        }).collect();
+        RaceAnalysis {
+            race,
+            time,
+            record,
+            best_hold,
+            best_distance,
+            margin: best_distance.map(|d| d.saturating_sub(record)),
+            window,
+            ways: find_record_breaking_count(time, record),
+            sensitivity,
+        }
+    }
+
+    /// Bar chart of distance against hold time, sampled down to width columns.
+    /// '#' holds beat the record, '.' holds don't and '-' marks the record.
+    fn chart(&self, width: usize, height: usize) -> String {
+        let columns = width.max(2).min(usize::try_from(self.time).unwrap_or(usize::MAX).saturating_add(1));
+        let height = height.max(1);
+        let holds: Vec<u128> = (0..columns)
+            .map(|x| {
+                // time * x / step without the time * x that could overflow
+                let step = (columns as u128 - 1).max(1);
+                self.time / step * x as u128 + self.time % step * x as u128 / step
+            })
+            .collect();
+        let top = self.best_distance.unwrap_or(u128::MAX).max(self.record).max(1) as f64;
+        let level = |distance: u128| (distance as f64 / top * height as f64).round() as usize;
+        let record_row = level(self.record);
+
+        let mut out = String::new();
+        for row in (1..=height).rev() {
+            for hold in &holds {
+                let distance = hold.saturating_mul(self.time - hold);
+                let c = if level(distance) >= row {
+                    if distance > self.record { '#' } else { '.' }
+                } else if row == record_row {
+                    '-'
+                } else {
+                    ' '
+                };
+                out.push(c);
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("hold 0{:>width$}\n", self.time, width = columns.saturating_sub(6).max(1)));
+        out
+    }
+}
+
+fn print_text(analysis: &RaceAnalysis, width: usize, height: usize) {
+    println!("Race {}: time {}, record {}", analysis.race, analysis.time, analysis.record);
+    match (analysis.best_distance, analysis.margin) {
+        (Some(distance), Some(margin)) => println!("\tbest hold: {} (distance {}, {} over the record)", analysis.best_hold, distance, margin),
+        _ => println!("\tbest hold: {} (distance too big for a u128)", analysis.best_hold),
+    }
+    match analysis.window {
+        Some((lo, hi)) => println!("\twinning holds: {} to {} ({} ways)", lo, hi, analysis.ways),
+        None => println!("\tno hold beats the record"),
+    }
+    println!("\t{:>8} {:>20} {:>20}", "record %", "record", "ways");
+    for s in &analysis.sensitivity {
+        println!("\t{:>7}% {:>20} {:>20}", s.percent, s.record, s.ways);
+    }
+    print!("{}", analysis.chart(width, height));
+    println!();
 }
 
-fn find_record_breaking_count(time: u64, record: u64) -> u64 {
-    let mut count = 0;
-    println!("Computing count for time: {} record: {}", time, record);
-    for i in 1..=time {
-        let time_charge = i;
-        let time_move = time-i;
-        let distance = time_move * time_charge;
-        //println!("\tCharge: {}, Distance: {}", time_charge, distance);
-        if distance > record { count += 1 }
+/// One row per race and sensitivity record, repeating the race columns.
+/// best_distance and margin are left empty when they don't fit in a u128
+fn to_csv(analyses: &[RaceAnalysis]) -> String {
+    let mut out = String::from("race,time,record,best_hold,best_distance,margin,min_hold,max_hold,ways,percent,test_record,test_ways\n");
+    for a in analyses {
+        let (min_hold, max_hold) = match a.window {
+            Some((lo, hi)) => (lo.to_string(), hi.to_string()),
+            None => (String::new(), String::new())
+        };
+        let best_distance = a.best_distance.map(|d| d.to_string()).unwrap_or_default();
+        let margin = a.margin.map(|m| m.to_string()).unwrap_or_default();
+        for s in &a.sensitivity {
+            out.push_str(&format!("{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                a.race, a.time, a.record, a.best_hold, best_distance, margin,
+                min_hold, max_hold, a.ways, s.percent, s.record, s.ways));
+        }
+    }
+    out
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
-    count
 }
 
-fn main() -> Result<(), ParseError> {
+fn run() -> Result<(), ParseError> {
     let args = Args::parse();
 
     let input_file = File::open(args.input).unwrap();
     let input_ranges = parse(BufReader::new(input_file))?;
 
-    let time_vals = match all_consuming(time_parser)(input_ranges.get(0).unwrap())
+    let time_vals = match all_consuming(time_parser)(input_ranges.first().unwrap())
     {
-        Ok((_, v)) => v,
+        Ok((_, v)) => race_values("Time", &v, args.part)?,
         Err(e) => panic!("Time vals parsing: {}", e)
     };
 
-    println!("time: {:?}", time_vals);
-
     let distance_vals = match all_consuming(distance_parser)(input_ranges.get(1).unwrap())
     {
-        Ok((_, v)) => v,
+        Ok((_, v)) => race_values("Distance", &v, args.part)?,
         Err(e) => panic!("Distance vals parsing: {}", e)
     };
 
-    println!("distance: {:?}", distance_vals);
-
     assert_eq!(time_vals.len(), distance_vals.len());
 
+    if let Some(Command::Explore { format, width, height }) = args.command {
+        let analyses: Vec<RaceAnalysis> = time_vals.iter().zip(distance_vals.iter()).enumerate()
+            .map(|(race, (time, record))| RaceAnalysis::new(race + 1, *time, *record))
+            .collect();
+        match format {
+            ExploreFormat::Text => analyses.iter().for_each(|a| print_text(a, width, height)),
+            ExploreFormat::Csv => print!("{}", to_csv(&analyses)),
+            ExploreFormat::Json => println!("{}", serde_json::to_string_pretty(&analyses).unwrap()),
+        }
+        return Ok(());
+    }
+
+    println!("time: {:?}", time_vals);
+    println!("distance: {:?}", distance_vals);
+
     let mut answer = 1;
     for (time, distance) in time_vals.iter().zip(distance_vals.iter()) {
         let count = find_record_breaking_count(*time, *distance);
+        if args.verify {
+            assert_eq!(count, find_record_breaking_count_loop(*time, *distance));
+        }
         println!("Time: {}, Distance: {}, Records: {}", time, distance, count);
         if count > 0 { answer *= count }
     }
@@ -113,3 +371,86 @@ fn main() -> Result<(), ParseError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{find_record_breaking_count, find_record_breaking_count_loop, race_values, to_csv, ParseError, RaceAnalysis};
+
+    #[test]
+    fn test_closed_form() {
+        assert_eq!(find_record_breaking_count(7, 9), 4);
+        assert_eq!(find_record_breaking_count(15, 40), 8);
+        // 10 * 20 = 200 only ties the record
+        assert_eq!(find_record_breaking_count(30, 200), 9);
+        assert_eq!(find_record_breaking_count(71530, 940200), 71503);
+
+        for time in 0..60 {
+            for record in 0..(time * time / 4 + 3) {
+                assert_eq!(find_record_breaking_count(time, record), find_record_breaking_count_loop(time, record), "time {} record {}", time, record);
+            }
+        }
+    }
+
+    #[test]
+    fn test_huge_times() {
+        // Every hold but 0 and time wins
+        assert_eq!(find_record_breaking_count(u128::MAX, 0), u128::MAX - 1);
+        let time = 1_u128 << 100;
+        assert_eq!(find_record_breaking_count(time, time - 2), time - 1);
+        // Holding for 1 ties the record
+        assert_eq!(find_record_breaking_count(time, time - 1), time - 3);
+    }
+
+    #[test]
+    fn test_kerning() {
+        assert_eq!(race_values("Time", &["7", "15", "30"], 1).unwrap(), vec![7, 15, 30]);
+        assert_eq!(race_values("Time", &["7", "15", "30"], 2).unwrap(), vec![71530]);
+        // Past u64::MAX but still fits in a u128
+        assert_eq!(race_values("Distance", &["18446744073", "709551616"], 2).unwrap(), vec![u128::from(u64::MAX) + 1]);
+        let too_big = u128::MAX.to_string();
+        assert!(matches!(race_values("Distance", &[&too_big, "0"], 2), Err(ParseError::Overflow { label: "Distance", .. })));
+    }
+
+    #[test]
+    fn test_race_analysis() {
+        let analysis = RaceAnalysis::new(3, 30, 200);
+        assert_eq!(analysis.best_hold, 15);
+        assert_eq!(analysis.best_distance, Some(225));
+        assert_eq!(analysis.margin, Some(25));
+        assert_eq!(analysis.window, Some((11, 19)));
+        assert_eq!(analysis.ways, 9);
+        let at_record = analysis.sensitivity.iter().find(|s| s.percent == 100).unwrap();
+        assert_eq!((at_record.record, at_record.ways), (200, 9));
+
+        let chart = analysis.chart(31, 5);
+        assert_eq!(chart.lines().count(), 6);
+        assert!(chart.lines().all(|l| l.len() == 31));
+    }
+
+    #[test]
+    fn test_race_analysis_overflow() {
+        let analysis = RaceAnalysis::new(1, 100_000_000_000_000_000_000, 9);
+        assert_eq!(analysis.best_distance, None);
+        assert_eq!(analysis.margin, None);
+        let csv = to_csv(std::slice::from_ref(&analysis));
+        assert!(csv.lines().nth(1).unwrap().starts_with("1,100000000000000000000,9,50000000000000000000,,,"));
+        assert!(serde_json::to_string(&analysis).unwrap().contains("\"margin\":null"));
+        let chart = analysis.chart(40, 5);
+        assert!(chart.lines().last().unwrap().ends_with("100000000000000000000"));
+    }
+
+    #[test]
+    fn test_sensitivity_scaling() {
+        let record = u128::MAX / 2 - 7;
+        let analysis = RaceAnalysis::new(1, u128::MAX, record);
+        let scaled = |percent| analysis.sensitivity.iter().find(|s| s.percent == percent).unwrap().record;
+        assert_eq!(scaled(100), record);
+        assert_eq!(scaled(50), record / 2);
+        assert_eq!(scaled(110), record / 100 * 110 + record % 100 * 110 / 100);
+        assert!(scaled(110) > record);
+        assert_eq!(scaled(150), record / 100 * 150 + record % 100 * 150 / 100);
+
+        let analysis = RaceAnalysis::new(1, 30, u128::MAX - 7);
+        assert_eq!(analysis.sensitivity.iter().find(|s| s.percent == 150).unwrap().record, u128::MAX);
+    }
+}
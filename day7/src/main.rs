@@ -1,8 +1,9 @@
-use std::{fs::File, io::{BufRead, BufReader}, str::Chars, cmp::Ordering};
-use std::collections::HashSet;
+use std::{fs::File, io::{BufRead, BufReader}, cmp::Ordering};
+use std::collections::{HashMap, HashSet};
 
-use clap::Parser;
-use nom::{combinator::{all_consuming, map}, bytes::complete::{take_until, tag}, character::complete::{u64, alphanumeric1}, sequence::separated_pair, IResult};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use nom::{combinator::all_consuming, bytes::complete::tag, character::complete::{u64, alphanumeric1}, sequence::separated_pair, IResult};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -17,11 +18,61 @@ struct Args {
     /// Defaults to 1
     #[arg(short, long, default_value_t = 1)]
     part: u8,
+
+    /// Card ranking to use instead of the part's, weakest first, e.g. 23456789TJQKA.
+    /// There are no wildcards unless --wild is given too.
+    #[arg(long)]
+    cards: Option<String>,
+
+    /// Cards that are wildcards instead of the part's, e.g. J or JX
+    #[arg(long)]
+    wild: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show why each hand ended up where it did in the ranking
+    Explain {
+        #[arg(short, long, value_enum, default_value_t = ExplainFormat::Table)]
+        format: ExplainFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExplainFormat {
+    Table,
+    Json,
 }
 
 #[derive(Debug)]
 enum ParseError {
-    Error
+    Error,
+    /// A line isn't a hand and a bid. line and column are 1-based, column is where parsing stopped.
+    Syntax { line: usize, column: usize },
+    /// Every hand has to have as many cards as the first one. line is 1-based
+    HandSize { line: usize, expected: usize, found: usize },
+    /// A hand has a card that isn't in the rules' order
+    UnknownCard { line: usize, card: char },
+    /// A wildcard that isn't in the rules' order
+    UnknownWildcard(char),
+    /// A card shows up more than once in the rules' order
+    DuplicateCard(char),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "couldn't read input"),
+            Self::Syntax { line, column } => write!(f, "line {}, column {}: expected a hand and a bid like '32T3K 765'", line, column),
+            Self::HandSize { line, expected, found } => write!(f, "line {}: hand has {} cards, the first one had {}", line, found, expected),
+            Self::UnknownCard { line, card } => write!(f, "line {}: {} isn't one of the cards", line, card),
+            Self::UnknownWildcard(card) => write!(f, "wildcard {} isn't one of the cards", card),
+            Self::DuplicateCard(card) => write!(f, "card {} is in the ranking more than once", card),
+        }
+    }
 }
 
 /// Read an input file and return a Ok(Vec<String>) with one String per line
@@ -38,113 +89,299 @@ fn parse<T>(input_buffer: T) -> Result<Vec<String>, ParseError> where T: BufRead
     Ok(result)
 }
 
-#[derive(Debug, Ord, Eq)]
+#[derive(Debug)]
 struct Game {
     input: String,
-    hand: Vec<u8>,
+    hand: Vec<usize>,
     bid: u64,
-    hand_type: u8 // 5 of a kind = 5, 4 of a kind = 4, etc
+    /// How many of each card, biggest group first. This is what ranks the hand
+    signature: Vec<usize>,
+    hand_type: HandType,
+    /// The card the wildcards pretended to be, if there were any
+    wild_as: Option<char>,
 }
 
-impl PartialOrd for Game {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let hand_cmp = self.hand_type.cmp(&other.hand_type);
-        if hand_cmp != Ordering::Equal { 
-            return Some(hand_cmp);
-        }
-        
-        assert_eq!(self.hand.len(), other.hand.len());
-        for (s, o) in self.hand.iter().zip(other.hand.iter()) {
-            let card_cmp = s.cmp(o);
-            match s.cmp(o) {
-                Ordering::Equal => (),
-                _ => { return Some(card_cmp); }
+/// The name of a hand's signature, only for showing it.
+/// Hands get ranked by comparing their signatures, biggest group first, so for
+/// 7 cards four and three beats four and three singles, and three pairs beat two.
+/// Signatures that aren't one of the 5 card hands are just their groups.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+    Groups(Vec<usize>),
+}
+
+impl HandType {
+    /// How many of each card there are, biggest count first.
+    /// Wildcards always do best joining the biggest group, so they get added to it.
+    /// A hand of only wildcards is all one group.
+    fn signature(cards: &str, rules: &Rules) -> Vec<usize> {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        let mut wild = 0;
+        for c in cards.chars() {
+            if rules.is_wild(c) {
+                wild += 1;
+            } else {
+                *counts.entry(c).or_default() += 1;
             }
         }
-        
-        Some(Ordering::Equal)
+        let mut signature: Vec<usize> = counts.into_values().collect();
+        signature.sort_by(|a, b| b.cmp(a));
+        match signature.first_mut() {
+            Some(biggest) => *biggest += wild,
+            None if wild > 0 => signature.push(wild),
+            None => ()
+        }
+        signature
+    }
+
+    /// The card whose group the wildcards join in signature: the most common
+    /// card that isn't wild, the strongest one if there's a tie.
+    /// A hand of nothing but wildcards is best as the strongest card that isn't wild.
+    /// None if the hand has no wildcards, or the rules have no cards that aren't wild.
+    fn wildcard_target(cards: &str, rules: &Rules) -> Option<char> {
+        if !cards.chars().any(|c| rules.is_wild(c)) {
+            return None;
+        }
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for c in cards.chars().filter(|c| !rules.is_wild(*c)) {
+            *counts.entry(c).or_default() += 1;
+        }
+        counts.into_iter().max_by_key(|(c, count)| (*count, rules.rank(*c))).map(|(c, _)| c)
+            .or_else(|| rules.order.iter().rev().copied().find(|c| !rules.is_wild(*c)))
+    }
+
+    fn from_signature(signature: &[usize]) -> Self {
+        match signature {
+            [5] => HandType::FiveOfAKind,
+            [4, 1] => HandType::FourOfAKind,
+            [3, 2] => HandType::FullHouse,
+            [3, 1, 1] => HandType::ThreeOfAKind,
+            [2, 2, 1] => HandType::TwoPair,
+            [2, 1, 1, 1] => HandType::OnePair,
+            [1, 1, 1, 1, 1] => HandType::HighCard,
+            _ => HandType::Groups(signature.to_vec()),
+        }
+    }
+}
+
+/// Games rank by signature, then card by card from the left.
+/// Identical hands fall back to the bid so sorting is deterministic,
+/// which means two games are only equal if both hand and bid are.
+impl Ord for Game {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.signature.cmp(&other.signature)
+            .then_with(|| self.hand.cmp(&other.hand))
+            .then_with(|| self.bid.cmp(&other.bid))
+    }
+}
+
+impl PartialOrd for Game {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl PartialEq for Game {
     fn eq(&self, other: &Self) -> bool {
-        self.input == other.input &&
-        self.bid == other.bid
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-impl Game {
-    fn new(input: &str, bid: u64) -> Self {
-        assert_eq!(input.len(), 5);
-        let mut hand = vec![];
-        for c in input.chars() {
-            let num_val = match c {
-                'A' => 14,
-                'K' => 13,
-                'Q' => 12,
-                'J' => 1,
-                'T' => 10,
-                _ => c.to_digit(10).unwrap()
-            };
-            hand.push(num_val as u8);
+impl Eq for Game {}
+
+/// How cards rank and which ones are wildcards
+#[derive(Debug, Clone)]
+struct Rules {
+    /// Cards from weakest to strongest
+    order: Vec<char>,
+    /// Cards that count as whatever card makes the best hand
+    wildcards: HashSet<char>,
+}
+
+impl Rules {
+    /// Part 1: J is a jack, no wildcards
+    fn standard() -> Self {
+        Rules { order: "23456789TJQKA".chars().collect(), wildcards: HashSet::new() }
+    }
+
+    /// Part 2: J is a joker, weakest card but a wildcard
+    fn jokers() -> Self {
+        Rules { order: "J23456789TQKA".chars().collect(), wildcards: HashSet::from(['J']) }
+    }
+
+    /// Any deck: order is weakest card first, each card once, wildcards can be any of those cards
+    fn custom(order: &str, wildcards: &str) -> Result<Self, ParseError> {
+        let mut seen = HashSet::new();
+        if let Some(card) = order.chars().find(|c| !seen.insert(*c)) {
+            return Err(ParseError::DuplicateCard(card));
         }
-        
-        let set: HashSet<char> = HashSet::from_iter(input.chars().collect::<Vec<_>>());
-        let mut occurrences = set.iter().filter(|v| **v != 'J').map(|v| input.matches(*v).count()).collect::<Vec<usize>>();
-        occurrences.sort();
-        
-        let joker_count = input.matches('J').count();
-        // If all jokers, max score.
-        // Otherwise, jokers add on to the count of the most prominent card.
-        let hand_type = if joker_count == 5 { 7 } else {
-            let hand_type1 = occurrences.pop().unwrap();
-            let hand_type2 = occurrences.pop();
-            
-            match (hand_type1 + joker_count, hand_type2) {
-                (5, _) => 7,
-                (4, _) => 6,
-                (3, Some(2)) => 5, // full house
-                (3, _) => 4, // 3 of a kind
-                (2, Some(2)) => 3, // 2 pair
-                (2, _) => 2,
-                (_,_) => 1
+        match wildcards.chars().find(|w| !order.contains(*w)) {
+            Some(card) => Err(ParseError::UnknownWildcard(card)),
+            None => Ok(Rules { order: order.chars().collect(), wildcards: wildcards.chars().collect() }),
+        }
+    }
+
+    fn for_part(part: u8) -> Self {
+        match part {
+            1 => Rules::standard(),
+            2 => Rules::jokers(),
+            _ => panic!("Unknown part")
+        }
+    }
+
+    /// Strength of a card, 1 for the weakest
+    fn rank(&self, card: char) -> Option<usize> {
+        self.order.iter().position(|c| *c == card).map(|p| p + 1)
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        self.wildcards.contains(&card)
+    }
+}
+
+impl Game {
+    /// A hand can have any number of cards, but games only rank properly
+    /// against others of the same size. Err with the first card the rules don't know.
+    fn new(input: &str, bid: u64, rules: &Rules) -> Result<Self, char> {
+        let hand = input.chars()
+            .map(|c| rules.rank(c).ok_or(c))
+            .collect::<Result<Vec<usize>, char>>()?;
+        let signature = HandType::signature(input, rules);
+        let hand_type = HandType::from_signature(&signature);
+        let wild_as = HandType::wildcard_target(input, rules);
+        Ok(Self { input: String::from(input), hand, bid, signature, hand_type, wild_as })
+    }
+}
+
+/// What decided that a game ranks above the one just below it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+enum TieBreak {
+    /// The weakest game, nothing below it
+    Lowest,
+    /// It has a better hand type, going by signature
+    HandType,
+    /// Same type, the card at this position (1-based) is stronger
+    Card(usize),
+    /// Same cards, so the bid decided
+    Bid,
+    /// Same cards and bid, nothing tells them apart
+    Tie,
+}
+
+/// One line of `day7 explain`
+#[derive(Debug, Serialize)]
+struct Explanation {
+    rank: usize,
+    hand: String,
+    bid: u64,
+    hand_type: HandType,
+    wild_as: Option<char>,
+    tie_break: TieBreak,
+}
+
+/// Explain every game in an already sorted list
+fn explain(games: &[Game]) -> Vec<Explanation> {
+    games.iter().enumerate().map(|(index, game)| {
+        let tie_break = match index.checked_sub(1).map(|i| &games[i]) {
+            None => TieBreak::Lowest,
+            Some(below) if below.signature != game.signature => TieBreak::HandType,
+            Some(below) => match game.hand.iter().zip(below.hand.iter()).position(|(a, b)| a != b) {
+                Some(position) => TieBreak::Card(position + 1),
+                None if below.bid != game.bid => TieBreak::Bid,
+                None => TieBreak::Tie,
             }
         };
-        
-        //let hand_type = HashSet::from_iter(test.chars().collect::<Vec<_>>());
-        Self { input: String::from(input), hand: hand, bid:bid, hand_type:hand_type }
+        Explanation {
+            rank: index + 1,
+            hand: game.input.clone(),
+            bid: game.bid,
+            hand_type: game.hand_type.clone(),
+            wild_as: game.wild_as,
+            tie_break,
+        }
+    }).collect()
+}
+
+fn print_table(explanations: &[Explanation]) {
+    println!("{:>5}  {:<8} {:>6}  {:<14} {:<5} tie break", "rank", "hand", "bid", "type", "wild");
+    for e in explanations {
+        let wild_as = e.wild_as.map(String::from).unwrap_or(String::from("-"));
+        let tie_break = match e.tie_break {
+            TieBreak::Lowest => String::from("lowest"),
+            TieBreak::HandType => String::from("hand type"),
+            TieBreak::Card(position) => format!("card {}", position),
+            TieBreak::Bid => String::from("bid"),
+            TieBreak::Tie => String::from("tie"),
+        };
+        println!("{:>5}  {:<8} {:>6}  {:<14} {:<5} {}", e.rank, e.hand, e.bid, format!("{:?}", e.hand_type), wild_as, tie_break);
     }
 }
 
-fn line_parser(s: &str) -> IResult<&str, Game> {
-    map(
-        separated_pair(alphanumeric1, tag(" "), u64),
-        |(hand, bet)| {
-            Game::new(hand, bet)
-        })(s)
+fn line_parser(s: &str) -> IResult<&str, (&str, u64)> {
+    separated_pair(alphanumeric1, tag(" "), u64)(s)
     }
     
     
-    fn main() -> Result<(), ParseError> {
+    fn main() {
+        if let Err(e) = run() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    fn run() -> Result<(), ParseError> {
         let args = Args::parse();
         
         let input_file = File::open(args.input).unwrap();
         let input_ranges = parse(BufReader::new(input_file))?;
         
+        let rules = match (&args.cards, &args.wild) {
+            (Some(cards), wild) => Rules::custom(cards, wild.as_deref().unwrap_or(""))?,
+            (None, Some(wild)) => Rules::custom(&Rules::for_part(args.part).order.iter().collect::<String>(), wild)?,
+            (None, None) => Rules::for_part(args.part),
+        };
         let mut games = vec![];
-        for range in input_ranges {
-            match all_consuming(line_parser)(&range) {
-                Ok(g) => games.push(g.1),
-                Err(e) => panic!("Parse error! {}", e)
+        for (index, range) in input_ranges.iter().enumerate() {
+            match all_consuming(line_parser)(range) {
+                Ok((_, (hand, bid))) => {
+                    let game = Game::new(hand, bid, &rules)
+                        .map_err(|card| ParseError::UnknownCard { line: index + 1, card })?;
+                    if let Some(first) = games.first().map(|g: &Game| g.hand.len()) {
+                        if game.hand.len() != first {
+                            return Err(ParseError::HandSize { line: index + 1, expected: first, found: game.hand.len() });
+                        }
+                    }
+                    games.push(game);
+                },
+                Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                    return Err(ParseError::Syntax { line: index + 1, column: range.len() - e.input.len() + 1 })
+                },
+                Err(nom::Err::Incomplete(_)) => return Err(ParseError::Error),
             }
             
         }
         games.sort();
         //games.reverse();
+
+        if let Some(Command::Explain { format }) = args.command {
+            let explanations = explain(&games);
+            match format {
+                ExplainFormat::Table => print_table(&explanations),
+                ExplainFormat::Json => println!("{}", serde_json::to_string_pretty(&explanations).unwrap()),
+            }
+            return Ok(());
+        }
         
         let mut answer = 0;
         for (index, game) in games.iter().enumerate() {
-            println!("{}: {:?}", index, game);
+            println!("{}: {} {:?} bid {}", index + 1, game.input, game.hand_type, game.bid);
             answer += (index+1) * game.bid as usize;
         }
         
@@ -152,4 +389,151 @@ fn line_parser(s: &str) -> IResult<&str, Game> {
         
         Ok(())
     }
-    
\ No newline at end of file
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use proptest::prelude::*;
+
+    use crate::{explain, Game, HandType, ParseError, Rules, TieBreak};
+
+    fn classify(cards: &str, rules: &Rules) -> HandType {
+        Game::new(cards, 0, rules).unwrap().hand_type
+    }
+
+    fn hand() -> impl Strategy<Value = String> {
+        proptest::collection::vec(proptest::sample::select("23456789TJQKA".chars().collect::<Vec<_>>()), 5)
+            .prop_map(|cards| cards.into_iter().collect())
+    }
+
+    fn game() -> impl Strategy<Value = (String, u64, bool)> {
+        (hand(), 0..5_u64, any::<bool>())
+    }
+
+    fn build((hand, bid, jokers): &(String, u64, bool)) -> Game {
+        let rules = if *jokers { Rules::jokers() } else { Rules::standard() };
+        Game::new(hand, *bid, &rules).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn test_ordering_agrees(a in game(), b in game()) {
+            let (a, b) = (build(&a), build(&b));
+            prop_assert_eq!(a.partial_cmp(&b), Some(a.cmp(&b)));
+            prop_assert_eq!(a == b, a.cmp(&b) == Ordering::Equal);
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+        }
+
+        #[test]
+        fn test_ordering_transitive(a in game(), b in game(), c in game()) {
+            let mut games = [build(&a), build(&b), build(&c)];
+            games.sort();
+            prop_assert!(games[0] <= games[1] && games[1] <= games[2] && games[0] <= games[2]);
+        }
+
+        #[test]
+        fn test_same_hand_orders_by_bid(hand in hand(), bid1 in any::<u64>(), bid2 in any::<u64>()) {
+            let rules = Rules::standard();
+            let (a, b) = (Game::new(&hand, bid1, &rules).unwrap(), Game::new(&hand, bid2, &rules).unwrap());
+            prop_assert_eq!(a.cmp(&b), bid1.cmp(&bid2));
+        }
+    }
+
+    #[test]
+    fn test_ranking() {
+        let rules = Rules::standard();
+        // Same type, first card decides
+        assert!(Game::new("33332", 1, &rules).unwrap() > Game::new("2AAAA", 1, &rules).unwrap());
+        // Type beats cards
+        assert!(Game::new("22333", 1, &rules).unwrap() > Game::new("AAAKQ", 1, &rules).unwrap());
+        // Jokers are weakest but wild
+        let rules = Rules::jokers();
+        assert!(Game::new("JKKK2", 1, &rules).unwrap() < Game::new("QQQQ2", 1, &rules).unwrap());
+        assert!(Game::new("JKKK2", 1, &rules).unwrap() > Game::new("KKKQ2", 1, &rules).unwrap());
+    }
+
+    #[test]
+    fn test_part_rules() {
+        // J is a jack between T and Q in part 1, a wildcard weaker than 2 in part 2
+        let (standard, jokers) = (Rules::for_part(1), Rules::for_part(2));
+        assert!(standard.rank('T') < standard.rank('J') && standard.rank('J') < standard.rank('Q'));
+        assert!(jokers.rank('J') < jokers.rank('2'));
+        assert!(Game::new("JJJJJ", 1, &standard).unwrap() > Game::new("TTTTT", 1, &standard).unwrap());
+        assert!(Game::new("JJJJJ", 1, &jokers).unwrap() < Game::new("TTTTT", 1, &jokers).unwrap());
+        assert_eq!(classify("KTJJT", &standard), HandType::TwoPair);
+        assert_eq!(classify("KTJJT", &jokers), HandType::FourOfAKind);
+    }
+
+    #[test]
+    fn test_classify() {
+        let rules = Rules::standard();
+        assert_eq!(classify("32T3K", &rules), HandType::OnePair);
+        assert_eq!(classify("KK677", &rules), HandType::TwoPair);
+        assert_eq!(classify("T55J5", &rules), HandType::ThreeOfAKind);
+        assert_eq!(classify("23332", &rules), HandType::FullHouse);
+        assert_eq!(classify("23456", &rules), HandType::HighCard);
+
+        let rules = Rules::jokers();
+        assert_eq!(classify("T55J5", &rules), HandType::FourOfAKind);
+        assert_eq!(classify("JJJJJ", &rules), HandType::FiveOfAKind);
+        assert_eq!(classify("2233J", &rules), HandType::FullHouse);
+
+        // 7 card hands
+        let rules = Rules::standard();
+        assert_eq!(classify("2223334", &rules), HandType::Groups(vec![3, 3, 1]));
+        assert_eq!(classify("2222223", &rules), HandType::Groups(vec![6, 1]));
+        assert!(Game::new("2222223", 1, &rules).unwrap() > Game::new("2222233", 1, &rules).unwrap());
+        // The whole signature ranks, not just the two biggest groups
+        assert!(Game::new("2222333", 1, &rules).unwrap() > Game::new("2222345", 1, &rules).unwrap());
+        assert!(Game::new("223344A", 1, &rules).unwrap() > Game::new("2233AKQ", 1, &rules).unwrap());
+
+        // Custom deck with two wildcard ranks
+        let rules = Rules::custom("XYabc", "XY").unwrap();
+        assert_eq!(classify("XYabc", &rules), HandType::ThreeOfAKind);
+        assert_eq!(classify("XYaab", &rules), HandType::FourOfAKind);
+        assert!(Game::new("XYaab", 1, &rules).unwrap() < Game::new("aaaab", 1, &rules).unwrap());
+
+        // Cards and wildcards the rules don't know
+        assert_eq!(Game::new("T55X5", 1, &Rules::standard()).err(), Some('X'));
+        assert!(matches!(Rules::custom("abc", "Z"), Err(ParseError::UnknownWildcard('Z'))));
+        assert!(matches!(Rules::custom("AAK", ""), Err(ParseError::DuplicateCard('A'))));
+
+        // More cards than fit in a u8
+        let order: String = (0..300).map(|i| char::from_u32(0x4E00 + i).unwrap()).collect();
+        let rules = Rules::custom(&order, "").unwrap();
+        let card = |i: u32| char::from_u32(0x4E00 + i).unwrap().to_string();
+        assert!(Game::new(&card(299), 1, &rules).unwrap() > Game::new(&card(0), 1, &rules).unwrap());
+        assert!(Game::new(&card(256), 1, &rules).unwrap() > Game::new(&card(255), 1, &rules).unwrap());
+    }
+
+    #[test]
+    fn test_explain() {
+        let rules = Rules::jokers();
+        let mut games = vec![
+            Game::new("KTJJT", 220, &rules).unwrap(),
+            Game::new("QQQJA", 483, &rules).unwrap(),
+            Game::new("KK677", 28, &rules).unwrap(),
+            Game::new("KK677", 5, &rules).unwrap(),
+            Game::new("KK678", 1, &rules).unwrap(),
+            Game::new("JJJJJ", 7, &rules).unwrap(),
+            Game::new("JJJJJ", 7, &rules).unwrap(),
+        ];
+        games.sort();
+        let explanations = explain(&games);
+        let summary: Vec<(&str, TieBreak)> = explanations.iter().map(|e| (e.hand.as_str(), e.tie_break)).collect();
+        assert_eq!(summary, vec![
+            ("KK678", TieBreak::Lowest),
+            ("KK677", TieBreak::HandType),
+            ("KK677", TieBreak::Bid),
+            ("QQQJA", TieBreak::HandType),
+            ("KTJJT", TieBreak::Card(1)),
+            ("JJJJJ", TieBreak::HandType),
+            ("JJJJJ", TieBreak::Tie),
+        ]);
+        assert_eq!(explanations[5].wild_as, Some('A'));
+        assert_eq!(explanations[3].wild_as, Some('Q'));
+        assert_eq!(explanations[4].wild_as, Some('T'));
+        assert_eq!(explanations[1].wild_as, None);
+    }
+}
@@ -1,13 +1,14 @@
-use std::{fs::File, io::{BufRead, BufReader}, collections::HashMap};
+use std::{fs::File, io::{BufRead, BufReader}, collections::{HashMap, HashSet}};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use numtheory::{crt, lcm, CrtError};
 
 use nom::{
     bytes::complete::tag,
-    character::complete::{u64, alphanumeric1, multispace1},
+    character::complete::alphanumeric1,
     combinator::{map, all_consuming},
-    multi::separated_list1,
-    sequence::{delimited, tuple, separated_pair},
+    sequence::tuple,
     IResult,
 };
 
@@ -24,13 +25,103 @@ struct Args {
     /// Defaults to 1
     #[arg(short, long, default_value_t = 1)]
     part: u8,
+
+    /// Node to start from. Defaults to AAA for part 1
+    #[arg(long, conflicts_with = "start_suffix")]
+    start: Option<String>,
+
+    /// Start from every node ending with this. Defaults to A for part 2
+    #[arg(long)]
+    start_suffix: Option<String>,
+
+    /// Node to stop at. Defaults to ZZZ for part 1
+    #[arg(long, conflicts_with = "goal_suffix")]
+    goal: Option<String>,
+
+    /// Stop at any node ending with this. Defaults to Z for part 2
+    #[arg(long)]
+    goal_suffix: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Export the network as Graphviz DOT, with starts, goals and ghost cycles highlighted
+    Graph {
+        /// Only keep nodes reachable from the start nodes
+        #[arg(long)]
+        reachable: bool,
+    },
+}
+
+/// Which nodes a walk starts or stops on
+#[derive(Debug, Clone)]
+enum NodeMatch {
+    Exact(String),
+    Suffix(String),
+}
+
+impl NodeMatch {
+    /// Exact wins over suffix, if neither is given use the part's default
+    fn pick(exact: Option<String>, suffix: Option<String>, default: NodeMatch) -> Self {
+        match (exact, suffix) {
+            (Some(e), _) => NodeMatch::Exact(e),
+            (None, Some(s)) => NodeMatch::Suffix(s),
+            (None, None) => default,
+        }
+    }
+
+    fn matches(&self, node: &str) -> bool {
+        match self {
+            NodeMatch::Exact(e) => node == e,
+            NodeMatch::Suffix(s) => node.ends_with(s.as_str()),
+        }
+    }
+}
+
+/// AAA for an exact node, *Z for a suffix
+impl std::fmt::Display for NodeMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeMatch::Exact(e) => write!(f, "{}", e),
+            NodeMatch::Suffix(s) => write!(f, "*{}", s),
+        }
+    }
 }
 
 #[derive(Debug)]
 enum ParseError {
-    Error
+    Error,
+    /// The first line is missing or empty
+    NoInstructions,
+    /// Instructions are only L and R. column is 1-based
+    Instruction { column: usize, found: char },
+    /// A node line didn't parse. line and column are 1-based, column is where parsing stopped.
+    Syntax { line: usize, column: usize, expected: &'static str },
+    /// The same node is defined twice
+    DuplicateNode { line: usize, node: String },
+    /// A node points at a node that's never defined
+    UnknownNode { line: usize, node: String },
 }
 
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "couldn't read input"),
+            Self::NoInstructions => write!(f, "no instructions on the first line"),
+            Self::Instruction { column, found } => write!(f, "line 1, column {}: expected L or R, found '{}'", column, found),
+            Self::Syntax { line, column, expected } => write!(f, "line {}, column {}: expected {}", line, column, expected),
+            Self::DuplicateNode { line, node } => write!(f, "line {}: {} is already defined", line, node),
+            Self::UnknownNode { line, node } => write!(f, "line {}: {} isn't defined anywhere", line, node),
+        }
+    }
+}
+
+/// What a node line should look like, for errors
+const NODE_LINE: &str = "a node like 'AAA = (BBB, CCC)'";
+
 /// Read an input file and return a Ok(Vec<String>) with one String per line
 /// If something weird happens, return Err(ParseError::Error)
 fn parse<T>(input_buffer: T) -> Result<Vec<String>, ParseError> where T: BufRead {
@@ -61,62 +152,525 @@ fn branch_parser(s: &str) -> IResult<&str, (&str, &str, &str)> {
     )(s)
 }
 
-fn gcd(mut x: u64, mut y: u64) -> u64 {
-    while x != y {
-        if x > y {
-            x = x - y;
+/// The map with every node name swapped for its index, so a step is two vector lookups
+#[derive(Debug)]
+struct Network {
+    names: Vec<String>,
+    /// (left, right) for every node
+    edges: Vec<(u32, u32)>,
+    /// false for L, true for R
+    instructions: Vec<bool>,
+}
+
+/// Where a walk that's at the start of the instructions ends up after one full pass,
+/// and how many steps into the pass it first lands on a goal
+#[derive(Debug, Clone, Copy)]
+struct Jump {
+    end: u32,
+    first_goal: Option<u32>,
+}
+
+impl Network {
+    /// Instructions are on the first line, nodes from the third one on, blank lines are skipped.
+    /// Every node gets an index in the order it's defined.
+    fn from_lines(lines: &[String]) -> Result<Self, ParseError> {
+        let instructions = lines.first().ok_or(ParseError::NoInstructions)?
+            .chars().enumerate()
+            .map(|(i, c)| match c {
+                'L' => Ok(false),
+                'R' => Ok(true),
+                _ => Err(ParseError::Instruction { column: i + 1, found: c }),
+            })
+            .collect::<Result<Vec<bool>, ParseError>>()?;
+        if instructions.is_empty() {
+            return Err(ParseError::NoInstructions);
+        }
+
+        if lines.get(1).is_some_and(|l| !l.is_empty()) {
+            return Err(ParseError::Syntax { line: 2, column: 1, expected: "a blank line after the instructions" });
+        }
+
+        let mut branches = vec![];
+        let mut index: HashMap<&str, u32> = HashMap::new();
+        for (i, line) in lines.iter().enumerate().skip(2) {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, left, right) = match all_consuming(branch_parser)(line) {
+                Ok((_, b)) => b,
+                Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                    return Err(ParseError::Syntax { line: i + 1, column: line.len() - e.input.len() + 1, expected: NODE_LINE })
+                },
+                Err(nom::Err::Incomplete(_)) => return Err(ParseError::Syntax { line: i + 1, column: line.len() + 1, expected: NODE_LINE }),
+            };
+            if index.insert(key, branches.len() as u32).is_some() {
+                return Err(ParseError::DuplicateNode { line: i + 1, node: String::from(key) });
+            }
+            branches.push((i + 1, key, left, right));
+        }
+
+        let lookup = |line: usize, node: &str| {
+            index.get(node).copied().ok_or_else(|| ParseError::UnknownNode { line, node: String::from(node) })
+        };
+        let edges = branches.iter()
+            .map(|(line, _, left, right)| Ok((lookup(*line, left)?, lookup(*line, right)?)))
+            .collect::<Result<Vec<(u32, u32)>, ParseError>>()?;
+
+        Ok(Network {
+            names: branches.iter().map(|(_, key, _, _)| String::from(*key)).collect(),
+            edges,
+            instructions,
+        })
+    }
+
+    /// Indices of every node matching, in the order they're defined
+    fn matching(&self, m: &NodeMatch) -> Vec<u32> {
+        (0..self.names.len() as u32).filter(|n| m.matches(&self.names[*n as usize])).collect()
+    }
+
+    /// Node reached from node by the instruction at index
+    fn step(&self, node: u32, index: usize) -> u32 {
+        let (left, right) = self.edges[node as usize];
+        if self.instructions[index] { right } else { left }
+    }
+
+    /// Every node that can be reached from starts, including the starts
+    fn reachable(&self, starts: &[u32]) -> Vec<bool> {
+        let mut seen = vec![false; self.edges.len()];
+        let mut stack = starts.to_vec();
+        while let Some(node) = stack.pop() {
+            if !seen[node as usize] {
+                seen[node as usize] = true;
+                let (left, right) = self.edges[node as usize];
+                stack.extend([left, right]);
+            }
+        }
+        seen
+    }
+
+    /// The (node, went right) edges taken by a walk from start between steps skip and skip + count
+    fn edges_walked(&self, start: u32, skip: u64, count: u64) -> HashSet<(u32, bool)> {
+        let steps = self.instructions.len() as u64;
+        let mut node = start;
+        let mut walked = HashSet::new();
+        for step in 0..skip + count {
+            let index = (step % steps) as usize;
+            if step >= skip {
+                walked.insert((node, self.instructions[index]));
+            }
+            node = self.step(node, index);
+        }
+        walked
+    }
+
+    /// One Jump per node, each is a full pass over the instructions
+    fn jump_table(&self, goals: &[bool]) -> Vec<Jump> {
+        (0..self.edges.len() as u32).map(|start| {
+            let mut node = start;
+            let mut first_goal = None;
+            for index in 0..self.instructions.len() {
+                node = self.step(node, index);
+                if first_goal.is_none() && goals[node as usize] {
+                    first_goal = Some(index as u32 + 1);
+                }
+            }
+            Jump { end: node, first_goal }
+        }).collect()
+    }
+
+    /// Steps from start to the first goal, going a whole pass per lookup.
+    /// A pass always starts on one of the nodes, so starting one on a node
+    /// a second time means the walk is going round without any goals.
+    fn walk(&self, table: &[Jump], start: u32) -> Option<u64> {
+        let mut seen = vec![false; self.edges.len()];
+        let mut node = start;
+        let mut step: u64 = 0;
+        while !seen[node as usize] {
+            seen[node as usize] = true;
+            let jump = table[node as usize];
+            if let Some(offset) = jump.first_goal {
+                return Some(step + offset as u64);
+            }
+            step += self.instructions.len() as u64;
+            node = jump.end;
+        }
+        None
+    }
+}
+
+/// What one ghost's walk looks like once it starts repeating.
+/// The state of a walk is (node, instruction index), so it has to repeat within
+/// nodes * instructions steps, and from then on goes around the same cycle forever.
+#[derive(Debug)]
+struct GhostCycle {
+    start: String,
+    /// Steps before the cycle starts
+    tail: u64,
+    /// Steps around the cycle
+    length: u64,
+    /// Steps before the cycle where the ghost is on a goal node
+    tail_hits: Vec<u64>,
+    /// Steps into the cycle where the ghost is on a goal node, all < length
+    cycle_offsets: Vec<u64>,
+}
+
+impl GhostCycle {
+    fn find(network: &Network, start: u32, goals: &[bool]) -> Self {
+        let steps = network.instructions.len();
+        // First step each (node, instruction index) state was seen at, u64::MAX if never
+        let mut seen = vec![u64::MAX; network.edges.len() * steps];
+        let mut hits = vec![];
+        let mut current = start;
+        let mut step: u64 = 0;
+        loop {
+            let index = (step % steps as u64) as usize;
+            let state = current as usize * steps + index;
+            if seen[state] != u64::MAX {
+                let tail = seen[state];
+                let (tail_hits, cycle_hits): (Vec<u64>, Vec<u64>) = hits.into_iter().partition(|h| *h < tail);
+                return GhostCycle {
+                    start: network.names[start as usize].clone(),
+                    tail,
+                    length: step - tail,
+                    tail_hits,
+                    cycle_offsets: cycle_hits.into_iter().map(|h| h - tail).collect(),
+                };
+            }
+            seen[state] = step;
+            if goals[current as usize] {
+                hits.push(step);
+            }
+
+            current = network.step(current, index);
+            step += 1;
+        }
+    }
+
+    fn at_goal(&self, step: u64) -> bool {
+        if step < self.tail {
+            self.tail_hits.contains(&step)
+        } else {
+            self.cycle_offsets.contains(&((step - self.tail) % self.length))
+        }
+    }
+
+    /// First step (after leaving the start) that lands on a goal
+    fn first_hit(&self) -> Option<u64> {
+        self.tail_hits.iter().copied().find(|h| *h > 0)
+            .or_else(|| self.cycle_offsets.iter().map(|o| self.tail + o).find(|h| *h > 0))
+            .or_else(|| self.cycle_offsets.first().map(|o| self.tail + o + self.length))
+    }
+
+    /// The LCM shortcut assumes the goal hits are exactly the multiples of the first one.
+    /// That needs the cycle length to be a multiple of the first hit, and the hits
+    /// before and in the cycle to be the multiples that land there, no more and no fewer.
+    /// Cycle hits are compared mod length, so a hit on step 0 counts as the one a lap later.
+    fn fits_lcm(&self) -> bool {
+        let Some(first) = self.first_hit() else { return false };
+        if !self.length.is_multiple_of(first) {
+            return false;
+        }
+        let tail_multiples: Vec<u64> = (first..self.tail).step_by(first as usize).collect();
+        let tail_hits: Vec<u64> = self.tail_hits.iter().copied().filter(|h| *h > 0).collect();
+        let cycle_multiples: Vec<u64> = (0..self.length).step_by(first as usize).collect();
+        let mut cycle_hits: Vec<u64> = self.cycle_offsets.iter().map(|o| (self.tail + o) % self.length).collect();
+        cycle_hits.sort();
+        tail_hits == tail_multiples && cycle_hits == cycle_multiples
+    }
+}
+
+/// Why first_common_goal has no answer
+#[derive(Debug, PartialEq)]
+enum CommonGoalError {
+    /// The ghosts are never all on a goal at once
+    Never,
+    /// Some combination of cycles needs a step count too big for a u64, so the answer can't be trusted
+    Overflow,
+}
+
+/// The first step where every ghost is on a goal at the same time.
+/// Before all of them are in their cycles the steps get checked one by one,
+/// after that every combination of goal offsets gets solved with the CRT.
+fn first_common_goal(ghosts: &[GhostCycle]) -> Result<u64, CommonGoalError> {
+    let first = ghosts.first().ok_or(CommonGoalError::Never)?;
+    let max_tail = ghosts.iter().map(|g| g.tail).max().unwrap();
+    if let Some(step) = (1..max_tail).find(|s| ghosts.iter().all(|g| g.at_goal(*s))) {
+        return Ok(step);
+    }
+
+    let mut overflow = false;
+    let mut solutions: Vec<(u64, u64)> = first.cycle_offsets.iter()
+        .map(|o| ((first.tail + o) % first.length, first.length))
+        .collect();
+    for ghost in &ghosts[1..] {
+        let mut next = vec![];
+        for (a, m) in &solutions {
+            for o in &ghost.cycle_offsets {
+                match crt(*a, *m, (ghost.tail + o) % ghost.length, ghost.length) {
+                    Ok(solution) => next.push(solution),
+                    Err(CrtError::Overflow) => overflow = true,
+                    Err(_) => (),
+                }
+            }
+        }
+        solutions = next;
+    }
+
+    // Smallest step that's at least max_tail (and at least 1) for each solution
+    let lower = max_tail.max(1);
+    let steps: Vec<Option<u64>> = solutions.into_iter().map(|(a, m)| {
+        if a >= lower { Some(a) } else { (lower - a).div_ceil(m).checked_mul(m)?.checked_add(a) }
+    }).collect();
+    if overflow || steps.iter().any(|s| s.is_none()) {
+        return Err(CommonGoalError::Overflow);
+    }
+    steps.into_iter().flatten().min().ok_or(CommonGoalError::Never)
+}
+
+/// Colours for ghost cycles, reused if there are more ghosts than colours
+const CYCLE_COLOURS: [&str; 6] = ["red", "blue", "darkgreen", "purple", "orange", "brown"];
+
+/// Render the network as Graphviz DOT.
+/// Starts are green, goals are red, and the edges each ghost keeps going round once it's in
+/// its cycle are drawn thick in that ghost's colour. Only nodes marked in keep are drawn.
+fn to_dot(network: &Network, starts: &[u32], goals: &[bool], ghosts: &[GhostCycle], keep: &[bool]) -> String {
+    let cycles: Vec<HashSet<(u32, bool)>> = starts.iter().zip(ghosts)
+        .map(|(start, ghost)| network.edges_walked(*start, ghost.tail, ghost.length))
+        .collect();
+    // All the colours of the ghosts whose cycles use any of these edges
+    let colours = |edges: &[(u32, bool)]| {
+        (0..cycles.len())
+            .filter(|g| edges.iter().any(|e| cycles[*g].contains(e)))
+            .map(|g| CYCLE_COLOURS[g % CYCLE_COLOURS.len()])
+            .collect::<Vec<&str>>()
+    };
+
+    let mut out = String::from("digraph day8 {\n");
+    for (node, name) in network.names.iter().enumerate() {
+        if !keep[node] {
+            continue;
+        }
+        let fill = match (starts.contains(&(node as u32)), goals[node]) {
+            (true, true) => ", style=filled, fillcolor=\"palegreen:lightcoral\"",
+            (true, false) => ", style=filled, fillcolor=palegreen",
+            (false, true) => ", style=filled, fillcolor=lightcoral",
+            (false, false) => "",
+        };
+        out.push_str(&format!("    \"{}\" [label=\"{}\"{}];\n", name, name, fill));
+    }
+    for (node, (left, right)) in network.edges.iter().enumerate() {
+        if !keep[node] {
+            continue;
+        }
+        let edges: Vec<(u32, &str, &[bool])> = if left == right {
+            vec![(*left, "L/R", &[false, true])]
         } else {
-            y = y - x;
+            vec![(*left, "L", &[false]), (*right, "R", &[true])]
+        };
+        for (to, label, went_right) in edges {
+            let walked: Vec<(u32, bool)> = went_right.iter().map(|r| (node as u32, *r)).collect();
+            let colour = colours(&walked);
+            let style = if colour.is_empty() {
+                String::new()
+            } else {
+                format!(", color=\"{}\", penwidth=2", colour.join(":"))
+            };
+            out.push_str(&format!("    \"{}\" -> \"{}\" [label=\"{}\"{}];\n",
+                network.names[node], network.names[to as usize], label, style));
         }
     }
-    x
+    out.push_str("}\n");
+    out
 }
 
-fn lcm(x: u64, y: u64) -> u64 {
-    x * y / gcd(x, y)
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
 
-fn main() -> Result<(), ParseError> {
+fn run() -> Result<(), ParseError> {
     let args = Args::parse();
     
     let input_file = File::open(args.input).unwrap();
     let input_ranges = parse(BufReader::new(input_file))?;
+    let network = Network::from_lines(&input_ranges)?;
     
-    let steps = input_ranges.get(0).unwrap();
-    
-    let mut map: HashMap<&str, (&str, &str)> = HashMap::new();
-    let mut currents = vec![];
-    
-    for range in input_ranges[2..].iter() {
-        match all_consuming(branch_parser)(range) {
-            Ok(p) => {
-                map.insert(p.1.0, (p.1.1, p.1.2));
-                if p.1.0.ends_with('A') {
-                    currents.push(p.1.0);
-                }
-            },
-            Err(e) => panic!("Parsing error! {}", e)
-        }
+    let (default_start, default_goal) = match args.part {
+        1 => (NodeMatch::Exact(String::from("AAA")), NodeMatch::Exact(String::from("ZZZ"))),
+        2 => (NodeMatch::Suffix(String::from("A")), NodeMatch::Suffix(String::from("Z"))),
+        _ => panic!("Unknown part")
+    };
+    let start = NodeMatch::pick(args.start, args.start_suffix, default_start);
+    let goal = NodeMatch::pick(args.goal, args.goal_suffix, default_goal);
+
+    let currents = network.matching(&start);
+    let goals: Vec<bool> = network.names.iter().map(|n| goal.matches(n)).collect();
+
+    if let Some(Command::Graph { reachable }) = args.command {
+        let ghosts: Vec<GhostCycle> = currents.iter()
+            .map(|start| GhostCycle::find(&network, *start, &goals))
+            .collect();
+        let keep = if reachable { network.reachable(&currents) } else { vec![true; network.names.len()] };
+        print!("{}", to_dot(&network, &currents, &goals, &ghosts, &keep));
+        return Ok(());
     }
     
     println!("Starting points: {}", currents.len());
+    if currents.is_empty() {
+        println!("No node matches {}", start);
+        return Ok(());
+    }
+
+    let table = network.jump_table(&goals);
+    if let [single] = currents[..] {
+        match network.walk(&table, single) {
+            Some(steps) => println!("Steps: {}", steps),
+            None => println!("{} never reaches a node matching {}", network.names[single as usize], goal),
+        }
+        return Ok(());
+    }
     
-    let mut total_dist = 1;
-    for one in &currents {
-        let mut current = *one;
-        for (step, d) in steps.bytes().cycle().enumerate() {
-            let next = map.get(current).unwrap();
-            match d {
-                b'L' => current = next.0,
-                b'R' => current = next.1,
-                _ => panic!("Unknown step!")
-            };
-            if current.ends_with('Z') {
-                total_dist = lcm(total_dist, u64::try_from(step+1).unwrap());
-                break
+    // Walking until the (node, instruction) state repeats means a goal that
+    // can't be reached shows up as a cycle without goals instead of looping forever
+    let ghosts: Vec<GhostCycle> = currents.iter()
+        .map(|start| GhostCycle::find(&network, *start, &goals))
+        .collect();
+    for ghost in &ghosts {
+        println!("{}: tail {}, cycle {}, goals before cycle {:?}, goals in cycle {:?}",
+            ghost.start, ghost.tail, ghost.length, ghost.tail_hits, ghost.cycle_offsets);
+    }
+
+    match ghosts.iter().find(|g| !g.fits_lcm()) {
+        None => {
+            match ghosts.iter().filter_map(|g| g.first_hit()).try_fold(1, lcm) {
+                Some(shortcut) => println!("LCM shortcut holds: {}", shortcut),
+                None => println!("LCM shortcut holds, but the answer doesn't fit in a u64"),
             }
-        }        
+        },
+        Some(g) => println!("LCM shortcut doesn't hold, {} isn't a pure cycle hitting its goal once per period", g.start),
+    }
+
+    if let Some(g) = ghosts.iter().find(|g| g.first_hit().is_none()) {
+        println!("{} never reaches a node matching {}", g.start, goal);
+        return Ok(());
+    }
+
+    match first_common_goal(&ghosts) {
+        Ok(steps) => println!("Steps: {}", steps),
+        Err(CommonGoalError::Never) => println!("The ghosts are never all on a goal at once"),
+        Err(CommonGoalError::Overflow) => println!("The ghosts' cycles line up too far out for a u64"),
     }
-    println!("Steps: {}", total_dist);    
     Ok(())
-}    
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{first_common_goal, to_dot, CommonGoalError, GhostCycle, Network, NodeMatch, ParseError};
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_walks() {
+        let network = Network::from_lines(&lines("LLR\n\nAAA = (BBB, BBB)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)\nXXX = (XXX, XXX)")).unwrap();
+        let goal = NodeMatch::Exact(String::from("ZZZ"));
+        let goals: Vec<bool> = network.names.iter().map(|n| goal.matches(n)).collect();
+        let table = network.jump_table(&goals);
+
+        let ghost = GhostCycle::find(&network, 0, &goals);
+        assert_eq!(ghost.first_hit(), Some(6));
+        assert_eq!(network.walk(&table, 0), Some(6));
+        assert_eq!(first_common_goal(&[ghost]), Ok(6));
+
+        // Never gets to ZZZ, but still stops
+        let ghost = GhostCycle::find(&network, 3, &goals);
+        assert_eq!(ghost.first_hit(), None);
+        assert_eq!(network.walk(&table, 3), None);
+        assert_eq!(first_common_goal(&[ghost]), Err(CommonGoalError::Never));
+
+        let goal = NodeMatch::Suffix(String::from("B"));
+        let goals: Vec<bool> = network.names.iter().map(|n| goal.matches(n)).collect();
+        let ghost = GhostCycle::find(&network, 0, &goals);
+        assert_eq!(ghost.first_hit(), Some(1));
+        assert_eq!(network.walk(&network.jump_table(&goals), 0), Some(1));
+    }
+
+    #[test]
+    fn test_network_errors() {
+        assert!(matches!(
+            Network::from_lines(&lines("LR\n\nAAA = (BBB, AAA)")),
+            Err(ParseError::UnknownNode { line: 3, node }) if node == "BBB"
+        ));
+        assert!(matches!(
+            Network::from_lines(&lines("LR\n\nAAA = (AAA, AAA)\nAAA = (AAA, AAA)")),
+            Err(ParseError::DuplicateNode { line: 4, .. })
+        ));
+        assert!(matches!(
+            Network::from_lines(&lines("LXR\n\nAAA = (AAA, AAA)")),
+            Err(ParseError::Instruction { column: 2, found: 'X' })
+        ));
+        assert!(matches!(
+            Network::from_lines(&lines("LR\n\nAAA = (AAA AAA)")),
+            Err(ParseError::Syntax { line: 3, column: 11, .. })
+        ));
+        assert!(matches!(
+            Network::from_lines(&lines("LR\nAAA = (AAA, AAA)")),
+            Err(ParseError::Syntax { line: 2, column: 1, .. })
+        ));
+        assert_eq!(Network::from_lines(&lines("LR\n\nAAA = (AAA, AAA)\n\n")).unwrap().names, vec!["AAA"]);
+    }
+
+    #[test]
+    fn test_dot() {
+        let network = Network::from_lines(&lines("LR\n\nAAA = (ZZZ, BBB)\nBBB = (BBB, BBB)\nZZZ = (AAA, AAA)\nXXX = (AAA, AAA)")).unwrap();
+        let goals = vec![false, false, true, false];
+        let ghosts = vec![GhostCycle::find(&network, 0, &goals)];
+        let keep = network.reachable(&[0]);
+        assert_eq!(keep, vec![true, true, true, false]);
+
+        let dot = to_dot(&network, &[0], &goals, &ghosts, &keep);
+        assert!(dot.contains("\"AAA\" [label=\"AAA\", style=filled, fillcolor=palegreen];"));
+        assert!(dot.contains("\"ZZZ\" [label=\"ZZZ\", style=filled, fillcolor=lightcoral];"));
+        assert!(dot.contains("\"AAA\" -> \"ZZZ\" [label=\"L\", color=\"red\", penwidth=2];"));
+        assert!(dot.contains("\"ZZZ\" -> \"AAA\" [label=\"L/R\", color=\"red\", penwidth=2];"));
+        assert!(dot.contains("\"AAA\" -> \"BBB\" [label=\"R\"];"));
+        assert!(!dot.contains("XXX"));
+    }
+
+    #[test]
+    fn test_fits_lcm() {
+        let ghost = |tail, length, offset| GhostCycle {
+            start: String::from("AAA"), tail, length, tail_hits: vec![], cycle_offsets: vec![offset],
+        };
+        assert!(ghost(1, 5, 4).fits_lcm());
+        // First hit at 2 * length: LCM of first hits would be too big
+        assert!(!ghost(4, 3, 2).fits_lcm());
+        assert!(!ghost(1, 5, 3).fits_lcm());
+        assert_eq!(first_common_goal(&[ghost(4, 3, 2), ghost(1, 5, 4)]), Ok(15));
+
+        // Starting on a goal: the hit at step 0 stands for the one at step length
+        assert!(ghost(0, 2, 0).fits_lcm());
+        assert!(ghost(0, 3, 0).fits_lcm());
+        assert_eq!(first_common_goal(&[ghost(0, 2, 0), ghost(0, 3, 0)]), Ok(6));
+
+        // Cycles whose lcm doesn't fit in a u64 aren't "never"
+        let big = (1 << 33) + 1;
+        assert_eq!(first_common_goal(&[ghost(1, big, big - 1), ghost(1, big + 2, big + 1)]), Err(CommonGoalError::Overflow));
+    }
+
+    #[test]
+    fn test_fits_lcm_sample() {
+        // sample3's 22A cycle is 6 steps with goals at 3 and 6, both multiples of its first hit
+        let lines: Vec<String> = include_str!("../sample3.txt").lines().map(String::from).collect();
+        let network = Network::from_lines(&lines).unwrap();
+        let goals: Vec<bool> = network.names.iter().map(|n| n.ends_with('Z')).collect();
+        let start = network.names.iter().position(|n| n == "22A").unwrap() as u32;
+        let ghost = GhostCycle::find(&network, start, &goals);
+        assert_eq!((ghost.tail, ghost.length, ghost.cycle_offsets.clone()), (1, 6, vec![2, 5]));
+        assert_eq!(ghost.first_hit(), Some(3));
+        assert!(ghost.fits_lcm());
+    }
+}
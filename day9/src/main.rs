@@ -1,6 +1,10 @@
 use std::{fs::File, io::{BufRead, BufReader}};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{CheckedAdd, CheckedSub, One, Signed, ToPrimitive, Zero};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -15,11 +19,50 @@ struct Args {
     /// Defaults to 1
     #[arg(short, long, default_value_t = 1)]
     part: u8,
+
+    /// Which way to extrapolate. Defaults to forward for part 1, backward for part 2
+    #[arg(short, long, value_enum)]
+    direction: Option<Direction>,
+
+    /// How many values past the end (or before the start) to extrapolate
+    #[arg(short, long, default_value_t = 1)]
+    steps: usize,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Fit a polynomial to each sequence, the first value being at n = 0
+    Fit {
+        /// Evaluate each polynomial at these indices too
+        #[arg(short, long, allow_negative_numbers = true)]
+        at: Vec<i64>,
+    },
+}
+
+/// Which end of a sequence to extend
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    Forward,
+    Backward,
 }
 
 #[derive(Debug)]
 enum ParseError {
-    Error
+    Error,
+    /// A token that isn't a whole number. line is 1-based
+    NotANumber { line: usize, token: String },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "couldn't read input"),
+            Self::NotANumber { line, token } => write!(f, "line {}: '{}' isn't a whole number", line, token),
+        }
+    }
 }
 
 /// Read an input file and return a Ok(Vec<String>) with one String per line
@@ -36,36 +79,245 @@ fn parse<T>(input_buffer: T) -> Result<Vec<String>, ParseError> where T: BufRead
     Ok(result)
 }
 
-fn main() -> Result<(), ParseError> {
+/// Whitespace separated numbers on each line, of any size
+fn parse_sequences(lines: &[String]) -> Result<Vec<Vec<BigInt>>, ParseError> {
+    lines.iter().enumerate().map(|(i, line)| {
+        line.split_whitespace()
+            .map(|token| token.parse().map_err(|_| ParseError::NotANumber { line: i + 1, token: String::from(token) }))
+            .collect()
+    }).collect()
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), ParseError> {
     let args = Args::parse();
 
     let input_file = File::open(args.input).unwrap();
     let input_ranges = parse(BufReader::new(input_file))?;
 
-    let mut input_lines : Vec<Vec<i64>> = vec![];
+    let input_lines = parse_sequences(&input_ranges)?;
 
-    for range in input_ranges {
-        input_lines.push(range.split(' ').map(|v| v.parse().unwrap()).collect());
+    if let Some(Command::Fit { at }) = args.command {
+        for (i, line) in input_lines.iter().enumerate() {
+            match Polynomial::fit(line) {
+                Some(polynomial) => {
+                    println!("Line {}: {}", i + 1, polynomial);
+                    for n in &at {
+                        println!("\tp({}) = {}", n, polynomial.eval(&BigInt::from(*n)));
+                    }
+                },
+                None => println!("Line {}: differences never reach all zeros, not fitting", i + 1),
+            }
+        }
+        return Ok(());
     }
 
-    println!("Answer: {}", input_lines.into_iter().map(|v| get_next_element(v)).sum::<i64>());
+    let direction = args.direction.unwrap_or(match args.part {
+        1 => Direction::Forward,
+        2 => Direction::Backward,
+        _ => panic!("Unknown part")
+    });
+
+    println!("Answer: {}", input_lines.iter().map(|v| extrapolate(v, direction, args.steps)).sum::<BigInt>());
 
     Ok(())
 }
 
-fn get_next_element(input: Vec<i64>) -> i64 {
-    let mut dx : Vec<Vec<i64>> = vec![input];
-    let mut index = 0;
-    while let Some(array) = dx.get(index) {
-        if array.iter().all(|v| *v == 0) {
+/// Differences of differences of input, until a row is all zeros.
+/// A sequence that never settles ends with an empty row instead.
+/// None if a difference overflows T.
+fn difference_table<T>(input: &[T]) -> Option<Vec<Vec<T>>> where T: Clone + Zero + CheckedSub {
+    let mut dx: Vec<Vec<T>> = vec![input.to_vec()];
+    while let Some(array) = dx.last() {
+        if array.iter().all(|v| v.is_zero()) {
             break;
         }
-        dx.push(array.iter().zip(array[1..].iter()).into_iter().map(|(v, nextv)| nextv - v).collect());
-        index += 1;
+        let next = array.iter().zip(array[1..].iter())
+            .map(|(v, nextv)| nextv.checked_sub(v))
+            .collect::<Option<Vec<T>>>()?;
+        dx.push(next);
     }
+    Some(dx)
+}
+
+/// Same as extrapolate, but None as soon as anything overflows T
+fn checked_extrapolate<T>(input: &[T], direction: Direction, steps: usize) -> Option<T>
+where T: Clone + Zero + CheckedAdd + CheckedSub {
+    let table = difference_table(input)?;
+    let mut edges: Vec<T> = table.iter()
+        .map(|row| match direction {
+            Direction::Forward => row.last(),
+            Direction::Backward => row.first(),
+        }.cloned().unwrap_or_else(T::zero))
+        .collect();
+    for _ in 0..steps {
+        for i in (0..edges.len() - 1).rev() {
+            edges[i] = match direction {
+                Direction::Forward => edges[i].checked_add(&edges[i + 1])?,
+                Direction::Backward => edges[i].checked_sub(&edges[i + 1])?,
+            };
+        }
+    }
+    Some(edges.swap_remove(0))
+}
+
+/// The value steps places after the end of input (Forward) or before its start (Backward).
+/// Only the edge of each row of the table matters, extending it a step at a time from the bottom up.
+/// Done in i64 when everything fits, and with big integers when something doesn't.
+fn extrapolate(input: &[BigInt], direction: Direction, steps: usize) -> BigInt {
+    let small: Option<Vec<i64>> = input.iter().map(|v| v.to_i64()).collect();
+    match small.and_then(|small| checked_extrapolate(&small, direction, steps)) {
+        Some(value) => BigInt::from(value),
+        None => checked_extrapolate(input, direction, steps).unwrap(),
+    }
+}
+
+/// A polynomial in n with exact coefficients, lowest power first
+#[derive(Debug, PartialEq)]
+struct Polynomial {
+    coefficients: Vec<BigRational>,
+}
 
-    dx.reverse();
-    let new_val = dx.iter().fold(0, |sum, v| v.get(0).unwrap()-sum);
-    println!("new_val: {}, dx: {:?}", new_val, dx);
-    new_val
-}
\ No newline at end of file
+impl Polynomial {
+    /// The polynomial going through every value of input, taking the first one as n = 0.
+    /// Newton's forward difference formula gives p(n) = sum of Δᵏ(0) * C(n, k),
+    /// with C(n, k) = n(n - 1)...(n - k + 1) / k! multiplied out one factor at a time.
+    /// None if the differences never get to a row of zeros, as then there's nothing
+    /// left to check the fit against and any sequence would "fit".
+    fn fit(input: &[BigInt]) -> Option<Self> {
+        let table = difference_table(input)?;
+        if table.last()?.is_empty() {
+            return None;
+        }
+
+        let mut coefficients = vec![BigRational::zero()];
+        // C(n, k) for the current k, starting at C(n, 0) = 1
+        let mut binomial = vec![BigRational::one()];
+        for (k, row) in table.iter().enumerate() {
+            if k > 0 {
+                // Multiply by (n - (k - 1)) / k
+                let shift = BigRational::from_integer(BigInt::from(k - 1));
+                let divisor = BigRational::from_integer(BigInt::from(k));
+                let mut next = vec![BigRational::zero(); binomial.len() + 1];
+                for (power, c) in binomial.iter().enumerate() {
+                    next[power + 1] += c / &divisor;
+                    next[power] -= c * &shift / &divisor;
+                }
+                binomial = next;
+            }
+            let delta = BigRational::from_integer(row[0].clone());
+            coefficients.resize(binomial.len(), BigRational::zero());
+            for (power, c) in binomial.iter().enumerate() {
+                coefficients[power] += c * &delta;
+            }
+        }
+
+        while coefficients.len() > 1 && coefficients.last().unwrap().is_zero() {
+            coefficients.pop();
+        }
+        Some(Polynomial { coefficients })
+    }
+
+    /// p(n), using Horner's method
+    fn eval(&self, n: &BigInt) -> BigRational {
+        let n = BigRational::from_integer(n.clone());
+        self.coefficients.iter().rev().fold(BigRational::zero(), |acc, c| acc * &n + c)
+    }
+}
+
+impl std::fmt::Display for Polynomial {
+    /// Highest power first, e.g. 1/2 n^2 + 3/2 n - 1
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut first = true;
+        for (power, c) in self.coefficients.iter().enumerate().rev() {
+            if c.is_zero() && !(power == 0 && first) {
+                continue;
+            }
+            let sign = match (first, c.is_negative()) {
+                (true, true) => "-",
+                (true, false) => "",
+                (false, true) => " - ",
+                (false, false) => " + ",
+            };
+            let magnitude = c.abs();
+            let number = if magnitude.is_one() && power > 0 { String::new() } else { magnitude.to_string() };
+            let space = if number.is_empty() || power == 0 { "" } else { " " };
+            let variable = match power {
+                0 => String::new(),
+                1 => String::from("n"),
+                _ => format!("n^{}", power),
+            };
+            write!(f, "{}{}{}{}", sign, number, space, variable)?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use crate::{checked_extrapolate, extrapolate, parse_sequences, Direction, ParseError, Polynomial};
+
+    fn big(values: &[i64]) -> Vec<BigInt> {
+        values.iter().map(|v| BigInt::from(*v)).collect()
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        let squares = big(&[0, 1, 4, 9, 16]);
+        assert_eq!(extrapolate(&squares, Direction::Forward, 1), BigInt::from(25));
+        assert_eq!(extrapolate(&squares, Direction::Forward, 3), BigInt::from(49));
+        assert_eq!(extrapolate(&squares, Direction::Backward, 1), BigInt::from(1));
+        assert_eq!(extrapolate(&squares, Direction::Backward, 4), BigInt::from(16));
+        assert_eq!(extrapolate(&big(&[10, 13, 16, 21, 30, 45]), Direction::Backward, 1), BigInt::from(5));
+        assert_eq!(extrapolate(&big(&[7]), Direction::Forward, 2), BigInt::from(7));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let max = BigInt::from(i64::MAX);
+        // Overflows extending the edges
+        assert_eq!(checked_extrapolate(&[0, i64::MAX], Direction::Forward, 1), None);
+        assert_eq!(extrapolate(&big(&[0, i64::MAX]), Direction::Forward, 1), &max * 2);
+        // Overflows building the table
+        assert_eq!(checked_extrapolate(&[i64::MAX, i64::MIN, i64::MAX], Direction::Forward, 1), None);
+        assert_eq!(extrapolate(&big(&[i64::MAX, i64::MIN, i64::MAX]), Direction::Forward, 1), &max * 7 + 3);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let lines = vec![String::from("1 2 3"), String::from("4 five 6")];
+        assert!(matches!(
+            parse_sequences(&lines),
+            Err(ParseError::NotANumber { line: 2, token }) if token == "five"
+        ));
+
+        // Too big for an i64 is still a number
+        let lines = vec![String::from("0 100000000000000000000 200000000000000000000")];
+        let sequences = parse_sequences(&lines).unwrap();
+        assert_eq!(extrapolate(&sequences[0], Direction::Forward, 1).to_string(), "300000000000000000000");
+    }
+
+    #[test]
+    fn test_fit() {
+        let p = Polynomial::fit(&big(&[1, 3, 6, 10, 15, 21])).unwrap();
+        assert_eq!(p.to_string(), "1/2 n^2 + 3/2 n + 1");
+        assert_eq!(p.eval(&BigInt::from(-1)).to_string(), "0");
+        assert_eq!(p.eval(&BigInt::from(1_000_000_000_000i64)).to_string(), "500000000001500000000001");
+
+        assert_eq!(Polynomial::fit(&big(&[5, 3, 1, -1])).unwrap().to_string(), "-2 n + 5");
+        assert_eq!(Polynomial::fit(&big(&[0, 0, 0])).unwrap().to_string(), "0");
+        assert_eq!(Polynomial::fit(&big(&[0, 1, 8, 27, 64])).unwrap().to_string(), "n^3");
+
+        // Runs out of values before the differences settle
+        assert_eq!(Polynomial::fit(&big(&[1, 2, 4, 8, 16])), None);
+    }
+}
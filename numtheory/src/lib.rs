@@ -0,0 +1,168 @@
+//! Integer math shared between days: gcd, lcm, modular inverse and CRT.
+
+/// Greatest common divisor with Euclid's algorithm.
+/// gcd(0, y) is y, so gcd(0, 0) is 0.
+pub fn gcd(mut x: u64, mut y: u64) -> u64 {
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    x
+}
+
+/// Greatest common divisor with Stein's binary algorithm.
+/// Only shifts and subtractions, gives the same answers as gcd.
+pub fn binary_gcd(mut x: u64, mut y: u64) -> u64 {
+    if x == 0 || y == 0 {
+        return x | y;
+    }
+    // Powers of 2 both have in common
+    let shift = (x | y).trailing_zeros();
+    x >>= x.trailing_zeros();
+    loop {
+        y >>= y.trailing_zeros();
+        if x > y {
+            (x, y) = (y, x);
+        }
+        y -= x;
+        if y == 0 {
+            return x << shift;
+        }
+    }
+}
+
+/// Least common multiple, or None if it doesn't fit in a u64.
+/// Divides before multiplying so it only overflows when the answer does.
+/// lcm(0, y) is 0.
+pub fn lcm(x: u64, y: u64) -> Option<u64> {
+    if x == 0 || y == 0 {
+        return Some(0);
+    }
+    (x / gcd(x, y)).checked_mul(y)
+}
+
+/// Extended Euclid: (g, a, b) with x*a + y*b = g = gcd(x, y)
+pub fn ext_gcd(x: i128, y: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (x, y);
+    let (mut old_a, mut a) = (1, 0);
+    let (mut old_b, mut b) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_a, a) = (a, old_a - q * a);
+        (old_b, b) = (b, old_b - q * b);
+    }
+    if old_r < 0 {
+        (-old_r, -old_a, -old_b)
+    } else {
+        (old_r, old_a, old_b)
+    }
+}
+
+/// x such that a*x = 1 (mod m), or None if a and m aren't coprime
+pub fn mod_inverse(a: u64, m: u64) -> Option<u64> {
+    if m == 0 {
+        return None;
+    }
+    let (g, x, _) = ext_gcd(i128::from(a), i128::from(m));
+    if g != 1 {
+        return None;
+    }
+    u64::try_from(x.rem_euclid(i128::from(m))).ok()
+}
+
+/// Why crt has no answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrtError {
+    /// A modulus is 0
+    ZeroModulus,
+    /// No x satisfies both congruences
+    Inconsistent,
+    /// There are solutions, but lcm(m1, m2) doesn't fit in a u64
+    Overflow,
+}
+
+/// Generalized Chinese Remainder Theorem, the moduli don't have to be coprime.
+/// Solves x = a1 (mod m1), x = a2 (mod m2), giving (x, lcm(m1, m2)) with x the
+/// smallest non-negative solution.
+pub fn crt(a1: u64, m1: u64, a2: u64, m2: u64) -> Result<(u64, u64), CrtError> {
+    if m1 == 0 || m2 == 0 {
+        return Err(CrtError::ZeroModulus);
+    }
+    let (a1, m1, a2, m2) = (i128::from(a1 % m1), i128::from(m1), i128::from(a2 % m2), i128::from(m2));
+    let (g, p, _) = ext_gcd(m1, m2);
+    let diff = a2 - a1;
+    if diff % g != 0 {
+        return Err(CrtError::Inconsistent);
+    }
+    let modulus = u64::try_from((m1 / g) as u128 * m2 as u128).map_err(|_| CrtError::Overflow)?;
+    // x = a1 + m1 * k where m1 * k = diff (mod m2).
+    // Both factors of k are < 2^64, so multiply them as u128 so it can't overflow.
+    let m2g = (m2 / g) as u128;
+    let k = ((diff / g).rem_euclid(m2g as i128) as u128 * p.rem_euclid(m2g as i128) as u128) % m2g;
+    let x = (a1 as u128 + m1 as u128 * k) % u128::from(modulus);
+    Ok((x as u64, modulus))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{binary_gcd, crt, ext_gcd, gcd, lcm, mod_inverse, CrtError};
+
+    #[test]
+    fn test_gcd() {
+        for (x, y, g) in [(0, 0, 0), (0, 7, 7), (7, 0, 7), (12, 18, 6), (17, 5, 1), (u64::MAX, u64::MAX, u64::MAX), (1 << 40, 1 << 20, 1 << 20)] {
+            assert_eq!(gcd(x, y), g, "gcd({}, {})", x, y);
+            assert_eq!(binary_gcd(x, y), g, "binary_gcd({}, {})", x, y);
+        }
+        for x in 0..60 {
+            for y in 0..60 {
+                assert_eq!(gcd(x, y), binary_gcd(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lcm() {
+        assert_eq!(lcm(0, 5), Some(0));
+        assert_eq!(lcm(4, 6), Some(12));
+        assert_eq!(lcm(u64::MAX, u64::MAX), Some(u64::MAX));
+        // Would overflow if multiplied first
+        assert_eq!(lcm(1 << 40, 1 << 41), Some(1 << 41));
+        assert_eq!(lcm(u64::MAX, 2), None);
+    }
+
+    #[test]
+    fn test_ext_gcd() {
+        for (x, y) in [(240, 46), (46, 240), (0, 5), (5, 0), (-12, 18), (17, -5)] {
+            let (g, a, b) = ext_gcd(x, y);
+            assert!(g >= 0);
+            assert_eq!(x * a + y * b, g);
+        }
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(10, 17), Some(12));
+        assert_eq!(mod_inverse(6, 9), None);
+        assert_eq!(mod_inverse(5, 0), None);
+        assert_eq!(mod_inverse(5, 1), Some(0));
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(2, 3, 3, 5), Ok((8, 15)));
+        // Moduli that aren't coprime
+        assert_eq!(crt(1, 4, 3, 6), Ok((9, 12)));
+        assert_eq!(crt(0, 4, 1, 6), Err(CrtError::Inconsistent));
+        assert_eq!(crt(0, 1, 5, 7), Ok((5, 7)));
+        // Residues bigger than the modulus
+        assert_eq!(crt(17, 5, 3, 7), Ok((17, 35)));
+        assert_eq!(crt(0, 0, 1, 2), Err(CrtError::ZeroModulus));
+        // lcm doesn't fit
+        assert_eq!(crt(0, u64::MAX, 0, u64::MAX - 1), Err(CrtError::Overflow));
+        // Contradiction wins over overflow
+        assert_eq!(crt(0, u64::MAX - 1, 1, u64::MAX - 3), Err(CrtError::Inconsistent));
+        let big = (1 << 32) - 5;
+        assert_eq!(crt(1, big, 0, big + 2).map(|(_, m)| m), Ok(big * (big + 2)));
+    }
+}